@@ -12,6 +12,7 @@
 //! and help messages.
 
 use getoptsargs::prelude::*;
+use std::time::Duration;
 
 fn app_extra_help(output: &mut dyn std::io::Write) -> std::io::Result<()> {
     writeln!(output, "This is an extra help message.")
@@ -24,16 +25,57 @@ fn app_setup(builder: Builder) -> Builder {
         .homepage("https://everything.example.com/")
         .manpage("the-everything", "8")
         .extra_help(app_extra_help)
+        .response_files()
         // Configure option processing.
         .optflag("p", "print-args", "print free arguments")
+        .optchoice("", "color", "when to use colors", "WHEN", &["auto", "always", "never"])
+        .optflag("", "json", "print output in JSON format")
+        .optflag("", "yaml", "print output in YAML format")
+        .exclusive(&["json", "yaml"])
+        .optopt("o", "output", "write the output to FILE", "FILE")
+        .optbool("", "headers", true, "print headers in the output")
+        .optmap("D", "define", "define a variable; can be given multiple times")
+        .optlist("", "features", "enable the given features", "F", ',')
+        .optduration("", "timeout", "give up after TIME", "TIME", Some(Duration::from_secs(150)))
+        .optbytes("", "buffer-size", "size of the I/O buffer", "SIZE", Some(64 * 1024))
         // Configure argument processing.
         .posarg("first", "this is the first required argument and contains a very long description")
         .posarg("second", "short description")
         .posarg("third_has_a_very_long_name", "and a short description")
         .trailarg("name", 0, usize::MAX, "file names")
+        // Configure constraints between options and arguments.
+        .conflicts("output", "print-args")
+        .requires_trailing("output", 1, 1)
 }
 
 fn app_main(matches: Matches) -> Result<i32> {
+    if let Some(color) = matches.opt_choice::<String>("color") {
+        println!("Color: {}", color);
+    }
+    if matches.opt_present("json") {
+        println!("Format: JSON");
+    } else if matches.opt_present("yaml") {
+        println!("Format: YAML");
+    }
+    if let Some(output) = matches.opt_str("output") {
+        println!("Output: {}", output);
+    }
+    if !matches.opt_bool("headers") {
+        println!("Headers: disabled");
+    }
+    for (key, value) in matches.opt_map("define", DuplicateKeys::Reject)? {
+        println!("Variable: {} = {}", key, value);
+    }
+    if matches.opt_present("timeout") || matches.opt_present("buffer-size") {
+        let timeout = matches.opt_duration("timeout")?.expect("Has a default");
+        let buffer_size = matches.opt_bytes("buffer-size")?.expect("Has a default");
+        println!("Timeout: {}ms; buffer size: {} bytes", timeout.as_millis(), buffer_size);
+    }
+    let features = matches.opt_list::<String>("features")?;
+    if !features.is_empty() {
+        println!("Features: {}", features.join(" "));
+    }
+
     if matches.opt_present("print-args") {
         println!("First arg: {}", matches.arg_pos("first"));
         println!("Second arg: {}", matches.arg_pos("second"));
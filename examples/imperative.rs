@@ -14,7 +14,8 @@
 use getoptsargs::prelude::*;
 
 fn main() {
-    let result = Builder::new("imperative", env!("CARGO_PKG_VERSION"), std::env::args())
+    // Use the `OsString`-based entry point so that arguments can be arbitrary file names.
+    let result = Builder::new_os("imperative", env!("CARGO_PKG_VERSION"), std::env::args_os())
         .optflag("", "print-args", "print free arguments")
         .trailarg("trail", 0, usize::MAX, "free arguments")
         .start();
@@ -38,8 +39,11 @@ fn main() {
     // matches now contains the application-specific flags and can be handled as desired.
 
     if matches.opt_present("print-args") {
-        for arg in matches.arg_trail() {
-            println!("Free argument: {}", arg);
+        for arg in matches.arg_trail_os() {
+            match arg.to_str() {
+                Some(arg) => println!("Free argument: {}", arg),
+                None => println!("Free argument (not UTF-8): {:?}", arg),
+            }
         }
     }
 }
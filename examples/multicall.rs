@@ -0,0 +1,41 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An example app that provides multiple programs in a single binary, selected by the
+//! name under which the binary is invoked.
+
+use getoptsargs::prelude::*;
+
+fn applet_setup(builder: Builder) -> Builder {
+    builder.trailarg("word", 0, usize::MAX, "words to print")
+}
+
+fn lower_main(matches: Matches) -> Result<i32> {
+    println!("{}", matches.arg_trail().join(" ").to_lowercase());
+    Ok(0)
+}
+
+fn upper_main(matches: Matches) -> Result<i32> {
+    println!("{}", matches.arg_trail().join(" ").to_uppercase());
+    Ok(0)
+}
+
+fn app_setup(builder: Builder) -> Builder {
+    builder
+        .multicall()
+        .subcommand("lower", "prints words in lowercase", applet_setup, lower_main)
+        .subcommand("upper", "prints words in uppercase", applet_setup, upper_main)
+}
+
+fn app_main(_matches: Matches) -> Result<i32> {
+    Err(bad_usage!("No applet specified").into())
+}
+
+app!("multicall", app_setup, app_main);
@@ -0,0 +1,109 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An example app that exposes a tree of subcommands, each with its own options and
+//! arguments, and global options that are accepted at any level.  Unknown subcommands
+//! are looked up in the `PATH` as `subcommands-<name>` executables.
+
+use getoptsargs::prelude::*;
+
+fn add_setup(builder: Builder) -> Builder {
+    builder
+        .optflag("f", "force", "replace the entry if it already exists")
+        .posarg("name", "name of the entry to add")
+}
+
+fn add_main(matches: Matches) -> Result<i32> {
+    if matches.opt_present("force") {
+        println!("Replacing {}", matches.arg_pos("name"));
+    } else {
+        println!("Adding {}", matches.arg_pos("name"));
+    }
+    if matches.verbosity() != 0 {
+        println!("Verbosity level: {}", matches.verbosity());
+    }
+    Ok(0)
+}
+
+fn remove_setup(builder: Builder) -> Builder {
+    builder.trailarg("name", 1, usize::MAX, "names of the entries to remove")
+}
+
+fn remove_main(matches: Matches) -> Result<i32> {
+    for name in matches.arg_trail() {
+        println!("Removing {}", name);
+    }
+    Ok(0)
+}
+
+fn validate_url(url: &str) -> std::result::Result<(), String> {
+    if url.contains("://") { Ok(()) } else { Err("must be a URL".to_owned()) }
+}
+
+fn copy_setup(builder: Builder) -> Builder {
+    builder
+        .trailarg("source", 1, usize::MAX, "names of the entries to copy")
+        .posarg("target", "name of the entry to copy into")
+}
+
+fn copy_main(matches: Matches) -> Result<i32> {
+    for name in matches.arg_trail() {
+        println!("Copying {} into {}", name, matches.arg_pos("target"));
+    }
+    Ok(0)
+}
+
+fn remote_add_setup(builder: Builder) -> Builder {
+    builder
+        .posarg("name", "name of the remote")
+        .posarg("url", "location of the remote")
+        .optposarg("branch", "main", "branch to track")
+        .validate("url", validate_url)
+}
+
+fn remote_add_main(matches: Matches) -> Result<i32> {
+    println!(
+        "Adding remote {} at {} tracking {}",
+        matches.arg_pos("name"),
+        matches.arg_pos("url"),
+        matches.arg_pos("branch")
+    );
+    if matches.verbosity() != 0 {
+        println!("Verbosity level: {}", matches.verbosity());
+    }
+    Ok(0)
+}
+
+fn remote_setup(builder: Builder) -> Builder {
+    builder.subcommand("add", "adds a new remote", remote_add_setup, remote_add_main)
+}
+
+fn remote_main(_matches: Matches) -> Result<i32> {
+    println!("No remotes configured");
+    Ok(0)
+}
+
+fn app_setup(builder: Builder) -> Builder {
+    builder
+        .verbosity_flags()
+        .env("verbose", "SUBCOMMANDS_VERBOSE")
+        .config_files()
+        .external_subcommands()
+        .subcommand("add", "adds a new entry", add_setup, add_main)
+        .subcommand("remove", "removes one or more entries", remove_setup, remove_main)
+        .subcommand("copy", "copies one or more entries", copy_setup, copy_main)
+        .subcommand("remote", "manages remotes", remote_setup, remote_main)
+}
+
+fn app_main(_matches: Matches) -> Result<i32> {
+    Err(bad_usage!("No command specified").into())
+}
+
+app!("subcommands", app_setup, app_main);
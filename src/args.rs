@@ -10,13 +10,18 @@
 
 //! Positional argument parsing.
 
-use crate::errors::{UsageError, bad_usage};
+use crate::errors::{UsageError, bad_usage, parse_value};
+use crate::os_args;
+use crate::validators::{self, Validator};
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::str::FromStr;
 
 /// Start of the second column in usage messages.  Matches `getopts`.
-const COL2_START: usize = 24;
+pub(crate) const COL2_START: usize = 24;
 /// Max lenth of the second column in usage messages.  Matches `getopts`.
-const COL2_WIDTH: usize = 54;
+pub(crate) const COL2_WIDTH: usize = 54;
 
 /// Given an `unwrapped` text, reformats it to fit within `max_width`.  Every generated line
 /// _except_ the first one is prefixed with `pad_width` spaces.
@@ -46,7 +51,7 @@ fn wrap(unwrapped: &str, pad_width: usize, max_width: usize) -> String {
 
 /// Given two strings, formats them as two colums.  The second column starts at `start2` and will
 /// be wrapped to `width2` characters.  The resulting string may have newlines in it.
-fn format_two_columns(col1: String, col2: &str, start2: usize, width2: usize) -> String {
+pub(crate) fn format_two_columns(col1: String, col2: &str, start2: usize, width2: usize) -> String {
     let mut text = col1;
     if text.len() < start2 {
         while text.len() < start2 {
@@ -60,14 +65,71 @@ fn format_two_columns(col1: String, col2: &str, start2: usize, width2: usize) ->
     text
 }
 
+/// Text of an argument, with invalid UTF-8 sequences replaced, along with its exact value.
+type Value = (String, OsString);
+
+/// Converts the exact `value` of an argument into a `Value`.
+fn to_value(value: OsString) -> Value {
+    (value.to_string_lossy().into_owned(), value)
+}
+
 /// Representation of parsed free arguments.
 #[derive(Debug)]
 pub struct Matches {
     /// Mapping of named positional arguments to their values.
-    positional: HashMap<&'static str, String>,
+    positional: HashMap<&'static str, Value>,
 
     /// Trailing optional or repeated arguments.
     trailing: Vec<String>,
+
+    /// Same as `trailing` but with the exact values given in the command line.
+    trailing_os: Vec<OsString>,
+
+    /// Name of the trailing arguments, if they were registered.
+    trailing_name: Option<&'static str>,
+
+    /// Names of the optional positional arguments that took their default values.
+    defaulted: Vec<&'static str>,
+}
+
+impl Matches {
+    /// Returns true if the argument called `name` received any value from the command line.
+    pub(crate) fn given(&self, name: &str) -> bool {
+        if self.trailing_name == Some(name) {
+            !self.trailing.is_empty()
+        } else {
+            self.positional.contains_key(name) && !self.defaulted.contains(&name)
+        }
+    }
+
+    /// Returns the number of trailing arguments.
+    pub(crate) fn num_trailing(&self) -> usize {
+        self.trailing.len()
+    }
+
+    /// Returns the exact value of the positional argument `name`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the argument name has not been correctly processed.
+    pub(crate) fn positional_os(&self, name: &str) -> &OsStr {
+        &self.positional.get(name).unwrap().1
+    }
+
+    /// Returns the exact values of the trailing arguments.
+    pub(crate) fn trailing_os(&self) -> &[OsString] {
+        &self.trailing_os
+    }
+
+    /// Returns the value of the positional argument `name` for `what`, or an error if it is not
+    /// valid UTF-8.
+    fn positional_str(&self, name: &str, what: &str) -> Result<&str, UsageError> {
+        let (value, value_os) = self.positional.get(name).unwrap();
+        match value_os.to_str() {
+            Some(_) => Ok(value),
+            None => Err(os_args::invalid_utf8(what, value)),
+        }
+    }
 }
 
 /// Returns the brief specification for the trailing argument `name`.
@@ -85,18 +147,46 @@ fn trailing_brief(name: &str, min: usize, max: usize) -> String {
 /// The interface of this struct is supposed to mimic `getopts::Options`.
 #[derive(Default)]
 pub struct Arguments {
-    positional_spec: Vec<(&'static str, &'static str)>,
+    positional_spec: Vec<(&'static str, Option<&'static str>, &'static str)>,
     trailing_spec: Option<(&'static str, usize, usize, &'static str)>,
+    suffix_spec: Vec<(&'static str, &'static str)>,
+    validators: Vec<(String, Validator)>,
 }
 
 impl Arguments {
     /// Registers the next positional argumet with `name` and `description`.
+    ///
+    /// Positional arguments registered after the trailing spec receive their values from the end
+    /// of the command line, like the destination of `cp`.
     pub fn positional(&mut self, name: &'static str, description: &'static str) {
+        if self.trailing_spec.is_some() {
+            self.suffix_spec.push((name, description));
+            return;
+        }
+        assert!(
+            self.positional_spec.iter().all(|(_name, default, _description)| default.is_none()),
+            "Cannot register required positional arguments after optional ones"
+        );
+        self.positional_spec.push((name, None, description));
+    }
+
+    /// Registers the next positional argument with `name` and `description` as optional, taking
+    /// the `default` value when not provided.
+    ///
+    /// Optional positional arguments must come after all required ones and receive values from
+    /// left to right, but only once the required positional arguments and the minimum number of
+    /// trailing arguments are satisfied.
+    pub fn optional_positional(
+        &mut self,
+        name: &'static str,
+        default: &'static str,
+        description: &'static str,
+    ) {
         assert!(
             self.trailing_spec.is_none(),
-            "Cannot register positional arguments after setting the trailing spec"
+            "Cannot register optional positional arguments after setting the trailing spec"
         );
-        self.positional_spec.push((name, description));
+        self.positional_spec.push((name, Some(default), description));
     }
 
     /// Registers the remaining trailing arguments with a base name of `name` and a `description`.
@@ -116,15 +206,68 @@ impl Arguments {
         self.trailing_spec = Some((name, min, max, description));
     }
 
+    /// Returns true if an argument called `name` has been registered.
+    pub(crate) fn has(&self, name: &str) -> bool {
+        self.positional_spec.iter().any(|(n, _default, _description)| *n == name)
+            || self.trailing_spec.is_some_and(|(n, _min, _max, _description)| n == name)
+            || self.suffix_spec.iter().any(|(n, _description)| *n == name)
+    }
+
+    /// Attaches a `validator` to the argument called `name`.
+    pub(crate) fn validate(&mut self, name: &str, validator: Validator) {
+        assert!(self.has(name), "No argument '{}' defined", name);
+        self.validators.push((name.to_owned(), validator));
+    }
+
+    /// Checks the `value` of the argument called `name` against its validators, which can only
+    /// inspect values that are valid UTF-8.
+    fn check(&self, name: &str, value: &Value) -> Result<(), UsageError> {
+        let mut validators = self
+            .validators
+            .iter()
+            .filter(|(n, _validator)| n == name)
+            .map(|(_n, validator)| validator)
+            .peekable();
+        let what = format!("argument `{}`", name);
+        if validators.peek().is_some() && value.1.to_str().is_none() {
+            return Err(os_args::invalid_utf8(&what, &value.0));
+        }
+        validators::check(validators, &what, &value.0)
+    }
+
+    /// Returns the name of the trailing arguments, if registered.
+    pub(crate) fn trailing_name(&self) -> Option<&'static str> {
+        self.trailing_spec.map(|(name, _min, _max, _description)| name)
+    }
+
+    /// Returns true if no arguments have been registered.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.positional_spec.is_empty()
+            && self.trailing_spec.is_none()
+            && self.suffix_spec.is_empty()
+    }
+
     /// Generates a brief description of the arguments specification to be used in usage summaries.
     pub(crate) fn brief(&self) -> String {
-        let mut spec = self.positional_spec.iter().map(|s| s.0).collect::<Vec<_>>().join(" ");
+        let mut spec = self
+            .positional_spec
+            .iter()
+            .map(|(name, default, _description)| match default {
+                Some(_) => format!("[{}]", name),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
         if let Some((name, min, max, _description)) = self.trailing_spec {
             if !spec.is_empty() {
                 spec.push(' ');
             }
             spec += &trailing_brief(name, min, max);
         }
+        for (name, _description) in &self.suffix_spec {
+            spec.push(' ');
+            spec.push_str(name);
+        }
         spec
     }
 
@@ -133,14 +276,23 @@ impl Arguments {
     /// The output matches the format of `getopts::Matches::usage` and should be used to extend its
     /// return value.
     pub(crate) fn usage(&self) -> String {
-        if self.positional_spec.is_empty() && self.trailing_spec.is_none() {
+        if self.is_empty() {
             return String::new();
         }
 
         let mut text = String::from("Arguments:\n");
-        for (name, description) in &self.positional_spec {
-            text +=
-                &format_two_columns(format!("    {}", name), description, COL2_START, COL2_WIDTH);
+        for (name, default, description) in &self.positional_spec {
+            let text1 = match default {
+                Some(_) => format!("    [{}]", name),
+                None => format!("    {}", name),
+            };
+            let text2 = match default {
+                Some(default) if !default.is_empty() => {
+                    format!("{} (default: {})", description, default)
+                }
+                _ => description.to_string(),
+            };
+            text += &format_two_columns(text1, &text2, COL2_START, COL2_WIDTH);
             text.push('\n');
         }
         if let Some((name, min, max, description)) = self.trailing_spec {
@@ -149,27 +301,71 @@ impl Arguments {
                 &format_two_columns(format!("    {}", brief), description, COL2_START, COL2_WIDTH);
             text.push('\n');
         }
+        for (name, description) in &self.suffix_spec {
+            text +=
+                &format_two_columns(format!("    {}", name), description, COL2_START, COL2_WIDTH);
+            text.push('\n');
+        }
         text
     }
 
     /// Parses a collection of free-form arguments and returns a `Matches` object if they are valid
     /// according to the arguments specification.
-    pub(crate) fn parse(&self, free: Vec<String>) -> Result<Matches, UsageError> {
+    pub(crate) fn parse<S: Into<OsString>>(&self, free: Vec<S>) -> Result<Matches, UsageError> {
+        let mut free = free.into_iter().map(|value| to_value(value.into())).collect::<Vec<_>>();
+        let num_required = self
+            .positional_spec
+            .iter()
+            .filter(|(_name, default, _description)| default.is_none())
+            .count();
+
+        // Positional arguments after the trailing spec take their values from the end, but only
+        // once the required positional arguments at the beginning are satisfied.
+        if free.len() < num_required {
+            let (name, _default, _description) = self.positional_spec[free.len()];
+            return Err(bad_usage!("Required argument `{}` not provided", name));
+        }
+        if free.len() < num_required + self.suffix_spec.len() {
+            let (name, _description) = self.suffix_spec[0];
+            return Err(bad_usage!("Required argument `{}` not provided", name));
+        }
+        let suffix = free.split_off(free.len() - self.suffix_spec.len());
+
+        // Optional positional arguments only receive the values that are left after satisfying
+        // the required positional arguments and the minimum number of trailing arguments.
+        let min_trailing = self.trailing_spec.map_or(0, |(_name, min, _max, _description)| min);
+        let mut num_optional = free.len().saturating_sub(num_required + min_trailing);
+
         let mut iter = free.into_iter();
 
-        let mut positional = HashMap::with_capacity(self.positional_spec.len());
-        for (name, _description) in &self.positional_spec {
-            let value = match iter.next() {
-                Some(value) => value,
-                None => return Err(bad_usage!("Required argument `{}` not provided", name)),
+        let mut positional =
+            HashMap::with_capacity(self.positional_spec.len() + self.suffix_spec.len());
+        let mut defaulted = vec![];
+        for (name, default, _description) in &self.positional_spec {
+            let value = match default {
+                None => match iter.next() {
+                    Some(value) => value,
+                    None => return Err(bad_usage!("Required argument `{}` not provided", name)),
+                },
+                Some(_) if num_optional > 0 => {
+                    num_optional -= 1;
+                    iter.next().expect("Optional arguments are only assigned if available")
+                }
+                Some(default) => {
+                    let previous = positional.insert(*name, to_value(OsString::from(default)));
+                    assert!(previous.is_none());
+                    defaulted.push(*name);
+                    continue;
+                }
             };
+            self.check(name, &value)?;
             let previous = positional.insert(*name, value);
             assert!(previous.is_none());
         }
 
         let mut trailing = vec![];
         if let Some((name, min, max, _description)) = self.trailing_spec {
-            trailing = iter.collect::<Vec<String>>();
+            trailing = iter.collect::<Vec<Value>>();
             if trailing.len() < min {
                 if min == 1 {
                     return Err(bad_usage!(
@@ -187,23 +383,53 @@ impl Arguments {
             if trailing.len() > max {
                 return Err(bad_usage!("Too many arguments"));
             }
+            for value in &trailing {
+                self.check(name, value)?;
+            }
         } else {
             if iter.next().is_some() {
                 return Err(bad_usage!("Too many arguments"));
             }
         }
 
-        Ok(Matches { positional, trailing })
+        for ((name, _description), value) in self.suffix_spec.iter().zip(suffix) {
+            self.check(name, &value)?;
+            let previous = positional.insert(*name, value);
+            assert!(previous.is_none());
+        }
+
+        let trailing_name = self.trailing_spec.map(|(name, _min, _max, _description)| name);
+        let (trailing, trailing_os) = trailing.into_iter().unzip();
+        Ok(Matches { positional, trailing, trailing_name, trailing_os, defaulted })
     }
 }
 
 impl super::Builder {
     /// Trivial wrapper over `Arguments::positional`.
     pub fn posarg(mut self, name: &'static str, description: &'static str) -> Self {
+        assert!(
+            self.subcommands.is_empty() && !self.app.external_subcommands,
+            "Cannot register arguments and subcommands at the same time"
+        );
         self.args.positional(name, description);
         self
     }
 
+    /// Trivial wrapper over `Arguments::optional_positional`.
+    pub fn optposarg(
+        mut self,
+        name: &'static str,
+        default: &'static str,
+        description: &'static str,
+    ) -> Self {
+        assert!(
+            self.subcommands.is_empty() && !self.app.external_subcommands,
+            "Cannot register arguments and subcommands at the same time"
+        );
+        self.args.optional_positional(name, default, description);
+        self
+    }
+
     /// Trivial wrapper over `Arguments::trailing`.
     pub fn trailarg(
         mut self,
@@ -212,6 +438,10 @@ impl super::Builder {
         max: usize,
         description: &'static str,
     ) -> Self {
+        assert!(
+            self.subcommands.is_empty() && !self.app.external_subcommands,
+            "Cannot register arguments and subcommands at the same time"
+        );
         self.args.trailing(name, min, max, description);
         self
     }
@@ -220,24 +450,66 @@ impl super::Builder {
 impl super::Matches {
     /// Returns the positional name identified by `name`.
     ///
+    /// Optional positional arguments that were not provided return their default value.  Invalid
+    /// UTF-8 sequences in the value are replaced by the Unicode replacement character.
+    ///
     /// # Panics
     ///
     /// This function will panic if the argument name has not been correctly processed.
     #[inline(always)]
     pub fn arg_pos(&self, name: &str) -> &str {
-        self.args.positional.get(name).unwrap()
+        &self.args.positional.get(name).unwrap().0
+    }
+
+    /// Returns the positional argument identified by `name` parsed as a `T`.
+    ///
+    /// Returns a `UsageError` that names the argument and its value if the value is invalid.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the argument name has not been correctly processed.
+    pub fn arg_pos_get<T>(&self, name: &str) -> Result<T, UsageError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let what = format!("argument `{}`", name);
+        parse_value(&what, self.args.positional_str(name, &what)?)
     }
 
     /// Returns the trailing arguments after all registered positional arguments.
+    ///
+    /// Invalid UTF-8 sequences in the values are replaced by the Unicode replacement character.
     #[inline(always)]
     pub fn arg_trail(&self) -> &[String] {
         self.args.trailing.as_slice()
     }
+
+    /// Returns the trailing arguments after all registered positional arguments parsed as `T`s.
+    ///
+    /// Returns a `UsageError` that names the argument and the first invalid value, if any.
+    pub fn arg_trail_get<T>(&self) -> Result<Vec<T>, UsageError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let what = format!("argument `{}`", self.args.trailing_name.unwrap_or_default());
+        self.args
+            .trailing
+            .iter()
+            .zip(&self.args.trailing_os)
+            .map(|(value, value_os)| match value_os.to_str() {
+                Some(_) => parse_value(&what, value),
+                None => Err(os_args::invalid_utf8(&what, value)),
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::parse_matches;
 
     #[test]
     fn test_wrap_fits_ok() {
@@ -315,6 +587,40 @@ mod tests {
         assert_eq!("one [name1 .. nameN]", args.brief());
     }
 
+    #[test]
+    fn test_arguments_brief_optional_positional() {
+        let mut args = Arguments::default();
+        args.positional("one", "irrelevant");
+        args.optional_positional("two", "", "irrelevant");
+        args.trailing("name", 0, usize::MAX, "irrelevant");
+        assert_eq!("one [two] [name1 .. nameN]", args.brief());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot register required positional arguments after optional ones")]
+    fn test_arguments_positional_after_optional() {
+        let mut args = Arguments::default();
+        args.optional_positional("one", "", "irrelevant");
+        args.positional("two", "irrelevant");
+    }
+
+    #[test]
+    fn test_arguments_brief_positional_after_trailing() {
+        let mut args = Arguments::default();
+        args.positional("one", "irrelevant");
+        args.trailing("source", 1, usize::MAX, "irrelevant");
+        args.positional("target", "irrelevant");
+        assert_eq!("one source1 [.. sourceN] target", args.brief());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot register optional positional arguments after setting")]
+    fn test_arguments_optional_positional_after_trailing() {
+        let mut args = Arguments::default();
+        args.trailing("source", 1, usize::MAX, "irrelevant");
+        args.optional_positional("target", "", "irrelevant");
+    }
+
     #[test]
     fn test_arguments_usage_none() {
         let args = Arguments::default();
@@ -377,10 +683,41 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_arguments_usage_optional_positional() {
+        let mut args = Arguments::default();
+        args.positional("one", "flag one");
+        args.optional_positional("two", "x", "flag two");
+        args.optional_positional("three", "", "flag three");
+        assert_eq!(
+            r"Arguments:
+    one                 flag one
+    [two]               flag two (default: x)
+    [three]             flag three
+",
+            args.usage()
+        );
+    }
+
+    #[test]
+    fn test_arguments_usage_positional_after_trailing() {
+        let mut args = Arguments::default();
+        args.trailing("source", 1, usize::MAX, "files to copy");
+        args.positional("target", "destination");
+        assert_eq!(
+            r"Arguments:
+    source1 [.. sourceN]
+                        files to copy
+    target              destination
+",
+            args.usage()
+        );
+    }
+
     #[test]
     fn test_arguments_parse_none() {
         let args = Arguments::default();
-        let matches = args.parse(vec![]).unwrap();
+        let matches = args.parse(Vec::<String>::new()).unwrap();
         assert!(matches.positional.is_empty());
         assert!(matches.trailing.is_empty());
     }
@@ -391,8 +728,8 @@ mod tests {
         args.positional("one", "flag one");
         args.positional("two", "flag two");
         let matches = args.parse(vec!["foo".to_owned(), "bar".to_owned()]).unwrap();
-        assert_eq!("foo", matches.positional["one"]);
-        assert_eq!("bar", matches.positional["two"]);
+        assert_eq!("foo", matches.positional["one"].0);
+        assert_eq!("bar", matches.positional["two"].0);
         assert!(matches.trailing.is_empty());
     }
 
@@ -401,7 +738,7 @@ mod tests {
         let mut args = Arguments::default();
         args.positional("one", "flag one");
         args.positional("two", "flag two");
-        let err = args.parse(vec![]).unwrap_err();
+        let err = args.parse(Vec::<String>::new()).unwrap_err();
         assert_eq!(bad_usage!("Required argument `one` not provided"), err);
         let err = args.parse(vec!["foo".to_owned()]).unwrap_err();
         assert_eq!(bad_usage!("Required argument `two` not provided"), err);
@@ -422,7 +759,7 @@ mod tests {
         let mut args = Arguments::default();
         args.trailing("name", 0, usize::MAX, "list of names");
 
-        let matches = args.parse(vec![]).unwrap();
+        let matches = args.parse(Vec::<String>::new()).unwrap();
         assert!(matches.positional.is_empty());
         assert!(matches.trailing.is_empty());
 
@@ -453,17 +790,193 @@ mod tests {
     fn test_arguments_parse_only_trailing_required_not_enough() {
         let mut args = Arguments::default();
         args.trailing("name", 1, usize::MAX, "list of names");
-        let err = args.parse(vec![]).unwrap_err();
+        let err = args.parse(Vec::<String>::new()).unwrap_err();
         assert_eq!(bad_usage!("Trailing argument `name` requires at least 1 value"), err);
     }
 
+    #[test]
+    fn test_matches_arg_pos_get() {
+        let mut args = Arguments::default();
+        args.positional("count", "a number");
+        args.positional("name", "a string");
+
+        let matches = parse_matches(&[], &args, &[&["42", "foo"]]);
+        assert_eq!(42, matches.arg_pos_get::<u8>("count").unwrap());
+        assert_eq!("foo", matches.arg_pos_get::<String>("name").unwrap());
+
+        let matches = parse_matches(&[], &args, &[&["300", "foo"]]);
+        assert_eq!(
+            bad_usage!(
+                "Invalid value `300` for argument `count`: number too large to fit in target type"
+            ),
+            matches.arg_pos_get::<u8>("count").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_matches_arg_trail_get() {
+        let mut args = Arguments::default();
+        args.trailing("number", 0, usize::MAX, "numbers");
+
+        let matches = parse_matches(&[], &args, &[&[]]);
+        assert!(matches.arg_trail_get::<i32>().unwrap().is_empty());
+
+        let matches = parse_matches(&[], &args, &[&["--", "1", "-2", "3"]]);
+        assert_eq!(vec![1, -2, 3], matches.arg_trail_get::<i32>().unwrap());
+
+        let matches = parse_matches(&[], &args, &[&["1", "x", "y"]]);
+        assert_eq!(
+            bad_usage!("Invalid value `x` for argument `number`: invalid digit found in string"),
+            matches.arg_trail_get::<i32>().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_arguments_parse_optional_positional() {
+        let mut args = Arguments::default();
+        args.positional("one", "flag one");
+        args.optional_positional("two", "default two", "flag two");
+        args.optional_positional("three", "default three", "flag three");
+
+        let err = args.parse(Vec::<String>::new()).unwrap_err();
+        assert_eq!(bad_usage!("Required argument `one` not provided"), err);
+
+        let matches = args.parse(vec!["a".to_owned()]).unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!("default two", matches.positional["two"].0);
+        assert_eq!("default three", matches.positional["three"].0);
+        assert!(matches.given("one"));
+        assert!(!matches.given("two"));
+        assert!(!matches.given("three"));
+
+        let matches = args.parse(vec!["a".to_owned(), "b".to_owned()]).unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!("b", matches.positional["two"].0);
+        assert_eq!("default three", matches.positional["three"].0);
+        assert!(matches.given("two"));
+        assert!(!matches.given("three"));
+
+        let matches = args.parse(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]).unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!("b", matches.positional["two"].0);
+        assert_eq!("c", matches.positional["three"].0);
+
+        let err = args
+            .parse(vec!["a".to_owned(), "b".to_owned(), "c".to_owned(), "d".to_owned()])
+            .unwrap_err();
+        assert_eq!(bad_usage!("Too many arguments"), err);
+    }
+
+    #[test]
+    fn test_arguments_parse_optional_positional_and_trailing() {
+        let mut args = Arguments::default();
+        args.optional_positional("one", "default", "flag one");
+        args.trailing("name", 1, usize::MAX, "list of names");
+
+        let matches = args.parse(vec!["a".to_owned()]).unwrap();
+        assert_eq!("default", matches.positional["one"].0);
+        assert_eq!(vec!["a"], matches.trailing);
+        assert!(!matches.given("one"));
+        assert!(matches.given("name"));
+
+        let matches = args.parse(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]).unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!(vec!["b", "c"], matches.trailing);
+    }
+
+    #[test]
+    fn test_arguments_parse_positional_after_trailing() {
+        let mut args = Arguments::default();
+        args.positional("one", "flag one");
+        args.trailing("source", 1, 2, "files to copy");
+        args.positional("target", "destination");
+        args.positional("mode", "copy mode");
+
+        let matches = args
+            .parse(vec!["a".to_owned(), "b".to_owned(), "c".to_owned(), "d".to_owned()])
+            .unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!(vec!["b"], matches.trailing);
+        assert_eq!("c", matches.positional["target"].0);
+        assert_eq!("d", matches.positional["mode"].0);
+
+        let matches = args
+            .parse(vec![
+                "a".to_owned(),
+                "b1".to_owned(),
+                "b2".to_owned(),
+                "c".to_owned(),
+                "d".to_owned(),
+            ])
+            .unwrap();
+        assert_eq!(vec!["b1", "b2"], matches.trailing);
+        assert_eq!("c", matches.positional["target"].0);
+        assert_eq!("d", matches.positional["mode"].0);
+    }
+
+    #[test]
+    fn test_arguments_parse_positional_after_trailing_errors() {
+        let mut args = Arguments::default();
+        args.positional("one", "flag one");
+        args.trailing("source", 1, 2, "files to copy");
+        args.positional("target", "destination");
+
+        let err = args.parse(Vec::<String>::new()).unwrap_err();
+        assert_eq!(bad_usage!("Required argument `one` not provided"), err);
+        let err = args.parse(vec!["a".to_owned()]).unwrap_err();
+        assert_eq!(bad_usage!("Required argument `target` not provided"), err);
+        let err = args.parse(vec!["a".to_owned(), "b".to_owned()]).unwrap_err();
+        assert_eq!(bad_usage!("Trailing argument `source` requires at least 1 value"), err);
+        let err = args
+            .parse(vec![
+                "a".to_owned(),
+                "b1".to_owned(),
+                "b2".to_owned(),
+                "b3".to_owned(),
+                "c".to_owned(),
+            ])
+            .unwrap_err();
+        assert_eq!(bad_usage!("Too many arguments"), err);
+    }
+
+    #[test]
+    fn test_arguments_parse_validators() {
+        let mut args = Arguments::default();
+        args.positional("one", "flag one");
+        args.trailing("name", 0, usize::MAX, "list of names");
+        let nonempty = |value: &str| {
+            if value.is_empty() { Err("cannot be empty".to_owned()) } else { Ok(()) }
+        };
+        args.validate("one", Validator::new(nonempty));
+        args.validate("name", Validator::new(nonempty));
+        args.validate(
+            "name",
+            Validator::new(|value| {
+                if value.starts_with('x') { Ok(()) } else { Err("must start with x".to_owned()) }
+            }),
+        );
+
+        let matches = args.parse(vec!["a".to_owned(), "xb".to_owned(), "xc".to_owned()]).unwrap();
+        assert_eq!("a", matches.positional["one"].0);
+        assert_eq!(vec!["xb", "xc"], matches.trailing);
+
+        assert_eq!(
+            bad_usage!("Invalid value `` for argument `one`: cannot be empty"),
+            args.parse(vec!["".to_owned()]).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Invalid value `yc` for argument `name`: must start with x"),
+            args.parse(vec!["a".to_owned(), "xb".to_owned(), "yc".to_owned()]).unwrap_err()
+        );
+    }
+
     #[test]
     fn test_arguments_parse_positional_and_trailing() {
         let mut args = Arguments::default();
         args.positional("one", "flag one");
         args.trailing("name", 0, usize::MAX, "list of names");
         let matches = args.parse(vec!["a".to_owned(), "b".to_owned()]).unwrap();
-        assert_eq!("a", matches.positional["one"]);
+        assert_eq!("a", matches.positional["one"].0);
         assert_eq!(vec!["b"], matches.trailing);
     }
 }
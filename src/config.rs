@@ -0,0 +1,324 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Configuration files that provide values for options.
+
+use crate::Builder;
+use crate::errors::{UsageError, bad_usage};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory that holds the system-wide configuration files.
+const SYSTEM_DIR: &str = "/etc";
+
+/// Settings for the configuration files of an application.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    /// Name of the application, used to compute the location of the configuration files.
+    name: String,
+
+    /// Names of the subcommands that lead to the current command, used to select the sections of
+    /// the configuration files that apply to it.
+    pub(crate) section: Vec<String>,
+}
+
+/// A `name = value` setting in a configuration file.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Entry {
+    /// Line number of the setting, starting at 1.
+    pub(crate) line: usize,
+
+    /// Name of the option that the setting refers to.
+    pub(crate) name: String,
+
+    /// Raw value of the setting.
+    pub(crate) value: String,
+
+    /// Whether the setting is under the section of the current command instead of being
+    /// inherited from an enclosing one, which may hold settings for other commands.
+    pub(crate) scoped: bool,
+}
+
+/// Settings of a configuration file that apply to the current command.
+#[derive(Debug)]
+pub(crate) struct ConfigFile {
+    /// Path to the configuration file.
+    pub(crate) path: PathBuf,
+
+    /// Settings in the order in which they appear in the file.
+    pub(crate) entries: Vec<Entry>,
+}
+
+impl Config {
+    /// Returns the paths to the system-wide and user configuration files, in this order, using
+    /// `lookup` to query environment variables.
+    fn paths<L: Fn(&str) -> Option<OsString>>(&self, lookup: L) -> Vec<PathBuf> {
+        let mut paths = vec![Path::new(SYSTEM_DIR).join(format!("{}.conf", self.name))];
+        let user_dir = match lookup("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+            _ => lookup("HOME").map(|home| PathBuf::from(home).join(".config")),
+        };
+        if let Some(user_dir) = user_dir {
+            paths.push(user_dir.join(&self.name).join("config"));
+        }
+        paths
+    }
+
+    /// Loads the configuration files of the application, sorted by increasing precedence, using
+    /// `lookup` to query environment variables.
+    ///
+    /// The system-wide and user files are optional.  The `explicit` file, if any, must exist.
+    pub(crate) fn load<L: Fn(&str) -> Option<OsString>>(
+        &self,
+        explicit: Option<OsString>,
+        lookup: L,
+    ) -> Result<Vec<ConfigFile>, UsageError> {
+        let mut files = vec![];
+        for path in self.paths(lookup) {
+            files.extend(load(path, &self.section, false)?);
+        }
+        if let Some(path) = explicit {
+            files.extend(load(PathBuf::from(path), &self.section, true)?);
+        }
+        Ok(files)
+    }
+}
+
+/// Parses the `contents` of the configuration file at `path` and keeps the settings that apply
+/// to the command identified by `section`.
+///
+/// Settings that appear before any section header apply to all commands.  Settings under a
+/// `[name ...]` header apply to the subcommand with that name and to all of its subcommands.
+fn parse(path: &Path, contents: &str, section: &[String]) -> Result<ConfigFile, UsageError> {
+    let mut entries = vec![];
+    let mut applies = true;
+    let mut scoped = false;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = match header.strip_suffix(']') {
+                Some(header) => header.split_whitespace().collect::<Vec<&str>>(),
+                None => {
+                    return Err(bad_usage!("Expected `[name]` in {}:{}", path.display(), i + 1));
+                }
+            };
+            applies = header.len() <= section.len()
+                && header.iter().zip(section.iter()).all(|(name, command)| name == command);
+            scoped = applies && !header.is_empty() && header.len() == section.len();
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                if applies {
+                    entries.push(Entry {
+                        line: i + 1,
+                        name: name.trim().to_owned(),
+                        value: value.trim().to_owned(),
+                        scoped,
+                    });
+                }
+            }
+            _ => return Err(bad_usage!("Expected `name = value` in {}:{}", path.display(), i + 1)),
+        }
+    }
+    Ok(ConfigFile { path: path.to_owned(), entries })
+}
+
+/// Loads the configuration file at `path` and keeps the settings that apply to the command
+/// identified by `section`.
+///
+/// Returns `None` if the file does not exist and is not `required`.
+fn load(
+    path: PathBuf,
+    section: &[String],
+    required: bool,
+) -> Result<Option<ConfigFile>, UsageError> {
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&path, &contents, section).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(None),
+        Err(e) => Err(bad_usage!("Cannot read configuration file {}: {}", path.display(), e)),
+    }
+}
+
+impl Builder {
+    /// Makes the application read the values of options not given in the command line from
+    /// configuration files and adds a `--config` option to specify an additional file.
+    ///
+    /// The files are `/etc/<program>.conf`, `$XDG_CONFIG_HOME/<program>/config` (which defaults
+    /// to `$HOME/.config/<program>/config`) and the one given to `--config`, in increasing order
+    /// of precedence.  Environment variables attached to options via `env` take precedence over
+    /// all files.
+    ///
+    /// Each line of a file has the form `name = value`, where `name` is the name of an option
+    /// and `value` is its argument or, for flags, a boolean.  Settings for subcommands go under a
+    /// `[subcommand]` header.  Empty lines and lines starting with `#` are ignored.
+    ///
+    /// Settings outside of any header apply to all commands, so they are ignored by the commands
+    /// that do not define the option they name.  Settings under the header of a command must
+    /// name one of its options.
+    pub fn config_files(mut self) -> Self {
+        self.app.config = Some(Config { name: self.app.program_name.clone(), section: vec![] });
+        self.optopt("", "config", "read configuration from FILE", "FILE")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::TestDir;
+
+    /// Creates a configuration for the `app` program at the level of the `section` command.
+    fn config(section: &[&str]) -> Config {
+        Config { name: "app".to_owned(), section: section.iter().map(|s| s.to_string()).collect() }
+    }
+
+    /// Shorthand to create an `Entry` that is not under a section.
+    fn entry(line: usize, name: &str, value: &str) -> Entry {
+        Entry { line, name: name.to_owned(), value: value.to_owned(), scoped: false }
+    }
+
+    #[test]
+    fn test_config_paths() {
+        let lookup = |var: &str| match var {
+            "XDG_CONFIG_HOME" => Some(OsString::from("/xdg")),
+            "HOME" => Some(OsString::from("/home/user")),
+            _ => None,
+        };
+        assert_eq!(
+            vec![PathBuf::from("/etc/app.conf"), PathBuf::from("/xdg/app/config")],
+            config(&[]).paths(lookup)
+        );
+    }
+
+    #[test]
+    fn test_config_paths_home() {
+        let lookup = |var: &str| match var {
+            "XDG_CONFIG_HOME" => Some(OsString::new()),
+            "HOME" => Some(OsString::from("/home/user")),
+            _ => None,
+        };
+        assert_eq!(
+            vec![PathBuf::from("/etc/app.conf"), PathBuf::from("/home/user/.config/app/config")],
+            config(&[]).paths(lookup)
+        );
+    }
+
+    #[test]
+    fn test_config_paths_none() {
+        assert_eq!(vec![PathBuf::from("/etc/app.conf")], config(&[]).paths(|_var| None));
+    }
+
+    #[test]
+    fn test_config_load() {
+        let tmp = TestDir::new("config-load");
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::write(dir.join("app/config"), "a = 1\n").unwrap();
+        fs::write(dir.join("explicit"), "b = 2\n").unwrap();
+
+        let explicit = dir.join("explicit").into_os_string();
+        let files = config(&[])
+            .load(Some(explicit), |var| match var {
+                "XDG_CONFIG_HOME" => Some(dir.as_os_str().to_owned()),
+                _ => None,
+            })
+            .unwrap();
+        let files = files.iter().filter(|f| f.path.starts_with(dir)).collect::<Vec<_>>();
+        assert_eq!(2, files.len());
+        assert_eq!(dir.join("app/config"), files[0].path);
+        assert_eq!(vec![entry(1, "a", "1")], files[0].entries);
+        assert_eq!(dir.join("explicit"), files[1].path);
+        assert_eq!(vec![entry(1, "b", "2")], files[1].entries);
+    }
+
+    #[test]
+    fn test_config_load_explicit_missing() {
+        let tmp = TestDir::new("config-load-explicit-missing");
+        let dir = tmp.path();
+        let explicit = dir.join("missing");
+        let err =
+            config(&[]).load(Some(explicit.clone().into_os_string()), |_var| None).unwrap_err();
+        assert!(
+            err.message
+                .starts_with(&format!("Cannot read configuration file {}: ", explicit.display())),
+            "Got: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let contents = "# A comment\n\nfoo = bar\n  baz=  \n  # Another comment\nfoo = a = b\n";
+        let file = parse(Path::new("f"), contents, &[]).unwrap();
+        assert_eq!(
+            vec![entry(3, "foo", "bar"), entry(4, "baz", ""), entry(6, "foo", "a = b")],
+            file.entries
+        );
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let contents = "a = 1\n[remote]\nb = 2\n[remote  add]\nc = 3\n[other]\nd = 4\n";
+        let names = |section: &[&str]| {
+            parse(Path::new("f"), contents, &config(section).section)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|e| e.name)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vec!["a"], names(&[]));
+        assert_eq!(vec!["a", "b"], names(&["remote"]));
+        assert_eq!(vec!["a", "b", "c"], names(&["remote", "add"]));
+        assert_eq!(vec!["a", "d"], names(&["other", "add"]));
+    }
+
+    #[test]
+    fn test_parse_sections_scoped() {
+        let contents = "a = 1\n[remote]\nb = 2\n[remote add]\nc = 3\n";
+        let scoped = |section: &[&str]| {
+            parse(Path::new("f"), contents, &config(section).section)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|e| (e.name, e.scoped))
+                .collect::<Vec<(String, bool)>>()
+        };
+        assert_eq!(vec![("a".to_owned(), false)], scoped(&[]));
+        assert_eq!(vec![("a".to_owned(), false), ("b".to_owned(), true)], scoped(&["remote"]));
+        assert_eq!(
+            vec![("a".to_owned(), false), ("b".to_owned(), false), ("c".to_owned(), true)],
+            scoped(&["remote", "add"])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            bad_usage!("Expected `name = value` in some/file:2"),
+            parse(Path::new("some/file"), "a = 1\nfoo\n", &[]).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Expected `name = value` in some/file:1"),
+            parse(Path::new("some/file"), " = 1\n", &[]).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Expected `[name]` in some/file:3"),
+            parse(Path::new("some/file"), "\n\n[foo\n", &[]).unwrap_err()
+        );
+    }
+}
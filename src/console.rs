@@ -0,0 +1,135 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Output streams that can be captured by in-process tests.
+//!
+//! Apps that write their output to `stdout()` and `stderr()` instead of using `println!` and
+//! `eprintln!` can have it verified by `testutils::check_in_process`.  Outside of such tests,
+//! these streams behave exactly like the process' stdout and stderr.
+
+use std::cell::RefCell;
+use std::io;
+
+/// Contents of stdout and stderr captured in the current thread.
+type Captured = (Vec<u8>, Vec<u8>);
+
+thread_local! {
+    /// Buffers that receive the output of the current thread while it is being captured.
+    static CAPTURED: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+/// Identifies one of the output streams of the application.
+#[derive(Clone, Copy, Debug)]
+enum Target {
+    /// The standard output.
+    Stdout,
+
+    /// The standard error.
+    Stderr,
+}
+
+/// Handle to one of the output streams of the application.
+///
+/// Output written to this handle goes to the corresponding stream of the process except when
+/// running under `testutils::check_in_process`, which captures it.
+#[derive(Debug)]
+pub struct Stream(Target);
+
+impl io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURED.with_borrow_mut(|captured| match captured.as_mut() {
+            Some((stdout, stderr)) => {
+                match self.0 {
+                    Target::Stdout => stdout.extend_from_slice(buf),
+                    Target::Stderr => stderr.extend_from_slice(buf),
+                }
+                true
+            }
+            None => false,
+        });
+        if captured {
+            Ok(buf.len())
+        } else {
+            match self.0 {
+                Target::Stdout => io::stdout().write(buf),
+                Target::Stderr => io::stderr().write(buf),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0 {
+            Target::Stdout => io::stdout().flush(),
+            Target::Stderr => io::stderr().flush(),
+        }
+    }
+}
+
+/// Returns a handle to the standard output of the application.
+pub fn stdout() -> Stream {
+    Stream(Target::Stdout)
+}
+
+/// Returns a handle to the standard error of the application.
+pub fn stderr() -> Stream {
+    Stream(Target::Stderr)
+}
+
+/// Clears the capture buffers of the current thread when dropped, even on panic.
+struct CaptureGuard;
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURED.with_borrow_mut(|captured| *captured = None);
+    }
+}
+
+/// Runs `f` while capturing the output written to `stdout()` and `stderr()` in the current
+/// thread, and returns its result along with the captured stdout and stderr.
+pub(crate) fn capture<F: FnOnce() -> R, R>(f: F) -> (R, Vec<u8>, Vec<u8>) {
+    CAPTURED.with_borrow_mut(|captured| {
+        assert!(captured.is_none(), "Output capturing cannot be nested");
+        *captured = Some((vec![], vec![]));
+    });
+    let guard = CaptureGuard;
+    let result = f();
+    let (stdout, stderr) = CAPTURED
+        .with_borrow_mut(|captured| captured.take())
+        .expect("Captured output must still be present");
+    drop(guard);
+    (result, stdout, stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_capture() {
+        let (result, stdout, stderr) = capture(|| {
+            write!(stdout(), "out 1 ").unwrap();
+            writeln!(stderr(), "err").unwrap();
+            writeln!(stdout(), "out 2").unwrap();
+            5
+        });
+        assert_eq!(5, result);
+        assert_eq!(b"out 1 out 2\n", stdout.as_slice());
+        assert_eq!(b"err\n", stderr.as_slice());
+        CAPTURED.with_borrow(|captured| assert!(captured.is_none()));
+    }
+
+    #[test]
+    fn test_capture_panic() {
+        let result = std::panic::catch_unwind(|| capture(|| panic!("Oops")));
+        assert!(result.is_err());
+        CAPTURED.with_borrow(|captured| assert!(captured.is_none()));
+    }
+}
@@ -0,0 +1,544 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Constraints on the combinations of options and arguments that can be given.
+
+use crate::errors::{UsageError, bad_usage};
+use crate::opts::undefined;
+use crate::{Builder, Matches};
+use std::fmt;
+
+/// Reference to an option from a constraint.
+#[derive(Clone, Debug)]
+pub(crate) struct OptRef {
+    /// Name of the option to query matches.
+    name: String,
+
+    /// Name of the option as the user would type it.
+    display_name: String,
+}
+
+/// Reference to an option or an argument from a constraint.
+#[derive(Clone, Debug)]
+pub(crate) enum Target {
+    /// The target is an option.
+    Opt(OptRef),
+
+    /// The target is the positional or trailing argument with the given name.
+    Arg(String),
+}
+
+impl Target {
+    /// Returns true if the target received any value.
+    fn given(&self, matches: &Matches) -> bool {
+        match self {
+            Self::Opt(opt) => matches.opt_present(&opt.name),
+            Self::Arg(name) => matches.args.given(name),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Opt(opt) => write!(f, "option {}", opt.display_name),
+            Self::Arg(name) => write!(f, "argument `{}`", name),
+        }
+    }
+}
+
+/// A constraint on the combinations of options and arguments that can be given.
+#[derive(Clone, Debug)]
+pub(crate) enum Constraint {
+    /// At most one of the options can be given.
+    Exclusive(Vec<OptRef>),
+
+    /// At least one of the options must be given.
+    RequireOneOf(Vec<OptRef>),
+
+    /// If the first target is given, the second must be given too.
+    Requires(Target, Target),
+
+    /// The two targets cannot be given at the same time.
+    Conflicts(Target, Target),
+
+    /// If the target is given, the named trailing argument must receive between a minimum and a
+    /// maximum number of values.
+    RequiresTrailing(Target, &'static str, usize, usize),
+}
+
+/// Formats the names of `opts` as a list whose last two items are separated by `last_sep`.
+fn join(opts: &[&OptRef], last_sep: &str) -> String {
+    let names = opts.iter().map(|opt| opt.display_name.as_str()).collect::<Vec<&str>>();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => {
+            format!("{} {} {}", rest.join(", "), last_sep, last)
+        }
+        _ => names.join(""),
+    }
+}
+
+/// Converts the first letter of `text` to uppercase.
+fn capitalize(text: String) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text,
+    }
+}
+
+/// Describes how many `name` arguments are needed to satisfy a range of `min` to `max` values.
+fn describe_count(name: &str, min: usize, max: usize) -> String {
+    let (text, n) = match (min, max) {
+        (min, max) if min == max => (format!("exactly {}", min), min),
+        (min, usize::MAX) => (format!("at least {}", min), min),
+        (0, max) => (format!("at most {}", max), max),
+        (min, max) => (format!("between {} and {}", min, max), max),
+    };
+    if n == 1 {
+        format!("{} `{}` argument", text, name)
+    } else {
+        format!("{} `{}` arguments", text, name)
+    }
+}
+
+impl Constraint {
+    /// Returns the representation of the constraint for the usage synopsis, if any.
+    pub(crate) fn brief(&self) -> Option<String> {
+        match self {
+            Self::Exclusive(opts) => {
+                let names = opts.iter().map(|opt| opt.display_name.as_str()).collect::<Vec<_>>();
+                Some(format!("[{}]", names.join(" | ")))
+            }
+            _ => None,
+        }
+    }
+
+    /// Drops the values of the options in an exclusive group that did not come from the command
+    /// line if another option in the same group did, so that the command line takes precedence.
+    fn prefer_command_line(&self, matches: &mut Matches) {
+        if let Self::Exclusive(opts) = self
+            && opts.iter().any(|opt| matches.opt_given(&opt.name))
+        {
+            matches
+                .fallbacks
+                .retain(|fallback| !opts.iter().any(|opt| fallback.spec.is(&opt.name)));
+        }
+    }
+
+    /// Checks if the constraint holds for `matches` and returns the description of the violation
+    /// if it does not.
+    fn check(&self, matches: &Matches) -> Option<String> {
+        match self {
+            Self::Exclusive(opts) => {
+                let present =
+                    opts.iter().filter(|opt| matches.opt_present(&opt.name)).collect::<Vec<_>>();
+                if present.len() > 1 {
+                    return Some(format!(
+                        "Options {} are mutually exclusive",
+                        join(&present, "and")
+                    ));
+                }
+            }
+            Self::RequireOneOf(opts) => {
+                if !opts.iter().any(|opt| matches.opt_present(&opt.name)) {
+                    let opts = opts.iter().collect::<Vec<_>>();
+                    return Some(format!("One of {} is required", join(&opts, "or")));
+                }
+            }
+            Self::Requires(target, other) => {
+                if target.given(matches) && !other.given(matches) {
+                    return Some(format!("{} requires {}", capitalize(target.to_string()), other));
+                }
+            }
+            Self::Conflicts(target, other) => {
+                if target.given(matches) && other.given(matches) {
+                    return Some(format!(
+                        "{} conflicts with {}",
+                        capitalize(target.to_string()),
+                        other
+                    ));
+                }
+            }
+            Self::RequiresTrailing(target, name, min, max) => {
+                let count = matches.args.num_trailing();
+                if target.given(matches) && (count < *min || count > *max) {
+                    return Some(format!(
+                        "{} requires {}",
+                        capitalize(target.to_string()),
+                        describe_count(name, *min, *max)
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Checks that all `constraints` hold for `matches` and reports all violations at once.
+///
+/// Option values that did not come from the command line are discarded if they would violate an
+/// exclusive group along with options given in the command line.
+pub(crate) fn check(constraints: &[Constraint], matches: &mut Matches) -> Result<(), UsageError> {
+    for constraint in constraints {
+        constraint.prefer_command_line(matches);
+    }
+    let violations =
+        constraints.iter().filter_map(|constraint| constraint.check(matches)).collect::<Vec<_>>();
+    if violations.is_empty() { Ok(()) } else { Err(bad_usage!("{}", violations.join("; "))) }
+}
+
+impl Builder {
+    /// Returns a reference to the option called `name` for use in constraints, if defined.
+    fn opt_ref(&self, name: &str) -> Option<OptRef> {
+        self.opts
+            .iter()
+            .chain(self.inherited.opts.iter())
+            .find(|spec| spec.is(name))
+            .map(|spec| OptRef { name: spec.name().to_owned(), display_name: spec.display_name() })
+    }
+
+    /// Returns references to the options called `names` for use in constraints.
+    fn opt_refs(&self, names: &[&str]) -> Vec<OptRef> {
+        assert!(names.len() > 1, "Option groups require at least two options");
+        names.iter().map(|name| self.opt_ref(name).unwrap_or_else(|| undefined(name))).collect()
+    }
+
+    /// Returns a reference to the option or argument called `name` for use in constraints.
+    fn target(&self, name: &str) -> Target {
+        match self.opt_ref(name) {
+            Some(opt) => Target::Opt(opt),
+            None if self.args.has(name) => Target::Arg(name.to_owned()),
+            None => panic!("No option or argument '{}' defined", name),
+        }
+    }
+
+    /// Makes the options called `names` mutually exclusive so that at most one can be given.
+    ///
+    /// The group is shown in the usage synopsis as `[--a | --b]`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any of the option names is not defined.
+    pub fn exclusive(mut self, names: &[&str]) -> Self {
+        let opts = self.opt_refs(names);
+        self.constraints.push(Constraint::Exclusive(opts));
+        self
+    }
+
+    /// Requires at least one of the options called `names` to be given.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any of the option names is not defined.
+    pub fn require_one_of(mut self, names: &[&str]) -> Self {
+        let opts = self.opt_refs(names);
+        self.constraints.push(Constraint::RequireOneOf(opts));
+        self
+    }
+
+    /// Requires the option or argument called `other` to be given whenever the option or argument
+    /// called `name` is given.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any of the names is not defined.
+    pub fn requires(mut self, name: &str, other: &str) -> Self {
+        let constraint = Constraint::Requires(self.target(name), self.target(other));
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Prevents the options or arguments called `name` and `other` from being given at the same
+    /// time.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any of the names is not defined.
+    pub fn conflicts(mut self, name: &str, other: &str) -> Self {
+        let constraint = Constraint::Conflicts(self.target(name), self.target(other));
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Requires the trailing argument to receive between `min` and `max` values whenever the
+    /// option or argument called `name` is given.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `name` is not defined or if there is no trailing argument.
+    pub fn requires_trailing(mut self, name: &str, min: usize, max: usize) -> Self {
+        let trailing_name = match self.args.trailing_name() {
+            Some(trailing_name) => trailing_name,
+            None => panic!("No trailing argument defined"),
+        };
+        let constraint = Constraint::RequiresTrailing(self.target(name), trailing_name, min, max);
+        self.constraints.push(constraint);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Arguments;
+    use crate::fallbacks::{Fallback, Source};
+    use crate::opts::OptSpec;
+    use crate::test_helpers::parse_matches;
+    use getopts::{HasArg, Occur};
+
+    /// Creates the specifications of the `json`, `yaml` and `text` flags.
+    fn specs() -> Vec<OptSpec> {
+        ["json", "yaml", "text"]
+            .iter()
+            .map(|name| OptSpec::new("", name, "", "", HasArg::No, Occur::Optional))
+            .collect()
+    }
+
+    /// Parses `args` with `specs`.
+    fn parse(specs: &[OptSpec], args: &[&str]) -> Matches {
+        parse_with_args(specs, &Arguments::default(), args)
+    }
+
+    /// Parses `args` with `specs` and with the `arguments` specification.
+    fn parse_with_args(specs: &[OptSpec], arguments: &Arguments, args: &[&str]) -> Matches {
+        parse_matches(specs, arguments, &[args])
+    }
+
+    /// Creates a reference to the option called `name`.
+    fn opt(name: &str) -> Target {
+        Target::Opt(opt_refs(&[name]).remove(0))
+    }
+
+    /// Creates a reference to the argument called `name`.
+    fn arg(name: &str) -> Target {
+        Target::Arg(name.to_owned())
+    }
+
+    /// Creates references to the options called `names`.
+    fn opt_refs(names: &[&str]) -> Vec<OptRef> {
+        names
+            .iter()
+            .map(|name| OptRef { name: name.to_string(), display_name: format!("--{}", name) })
+            .collect()
+    }
+
+    #[test]
+    fn test_brief() {
+        assert_eq!(
+            Some("[--json | --yaml | --text]".to_owned()),
+            Constraint::Exclusive(opt_refs(&["json", "yaml", "text"])).brief()
+        );
+        assert_eq!(None, Constraint::RequireOneOf(opt_refs(&["json", "yaml"])).brief());
+    }
+
+    #[test]
+    fn test_exclusive() {
+        let specs = specs();
+        let constraints = [Constraint::Exclusive(opt_refs(&["json", "yaml", "text"]))];
+
+        check(&constraints, &mut parse(&specs, &[])).unwrap();
+        check(&constraints, &mut parse(&specs, &["--yaml"])).unwrap();
+        assert_eq!(
+            bad_usage!("Options --json and --yaml are mutually exclusive"),
+            check(&constraints, &mut parse(&specs, &["--yaml", "--json"])).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Options --json, --yaml and --text are mutually exclusive"),
+            check(&constraints, &mut parse(&specs, &["--text", "--yaml", "--json"])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_exclusive_command_line_wins() {
+        let specs = specs();
+        let constraints = [Constraint::Exclusive(opt_refs(&["json", "yaml"]))];
+
+        let mut matches = parse(&specs, &["--yaml"]);
+        matches.fallbacks = vec![
+            Fallback {
+                spec: specs[0].clone(),
+                values: vec![None],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+            Fallback {
+                spec: specs[2].clone(),
+                values: vec![None],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+        ];
+        check(&constraints, &mut matches).unwrap();
+        assert!(!matches.opt_present("json"));
+        assert!(matches.opt_present("yaml"));
+        assert!(matches.opt_present("text"));
+
+        let mut matches = parse(&specs, &[]);
+        matches.fallbacks = vec![
+            Fallback {
+                spec: specs[0].clone(),
+                values: vec![None],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+            Fallback {
+                spec: specs[1].clone(),
+                values: vec![None],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+        ];
+        assert_eq!(
+            bad_usage!("Options --json and --yaml are mutually exclusive"),
+            check(&constraints, &mut matches).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_require_one_of() {
+        let specs = specs();
+        let constraints = [Constraint::RequireOneOf(opt_refs(&["json", "yaml", "text"]))];
+
+        check(&constraints, &mut parse(&specs, &["--text"])).unwrap();
+        check(&constraints, &mut parse(&specs, &["--text", "--json"])).unwrap();
+        assert_eq!(
+            bad_usage!("One of --json, --yaml or --text is required"),
+            check(&constraints, &mut parse(&specs, &[])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_exclusive_and_require_one_of() {
+        let specs = specs();
+        let constraints = [
+            Constraint::Exclusive(opt_refs(&["json", "yaml"])),
+            Constraint::RequireOneOf(opt_refs(&["json", "yaml"])),
+        ];
+
+        check(&constraints, &mut parse(&specs, &["--json"])).unwrap();
+        assert_eq!(
+            bad_usage!("One of --json or --yaml is required"),
+            check(&constraints, &mut parse(&specs, &["--text"])).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Options --json and --yaml are mutually exclusive"),
+            check(&constraints, &mut parse(&specs, &["--json", "--yaml"])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_describe_count() {
+        assert_eq!("exactly 1 `file` argument", describe_count("file", 1, 1));
+        assert_eq!("exactly 2 `file` arguments", describe_count("file", 2, 2));
+        assert_eq!("at least 1 `file` argument", describe_count("file", 1, usize::MAX));
+        assert_eq!("at most 3 `file` arguments", describe_count("file", 0, 3));
+        assert_eq!("between 1 and 3 `file` arguments", describe_count("file", 1, 3));
+    }
+
+    #[test]
+    fn test_requires() {
+        let specs = specs();
+        let constraints = [Constraint::Requires(opt("json"), opt("yaml"))];
+
+        check(&constraints, &mut parse(&specs, &[])).unwrap();
+        check(&constraints, &mut parse(&specs, &["--yaml"])).unwrap();
+        check(&constraints, &mut parse(&specs, &["--yaml", "--json"])).unwrap();
+        assert_eq!(
+            bad_usage!("Option --json requires option --yaml"),
+            check(&constraints, &mut parse(&specs, &["--json"])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_requires_bool_fallback() {
+        let mut color = OptSpec::new("", "color", "", "", HasArg::No, Occur::Optional);
+        color.bool_default = Some(true);
+        let specs = [color, OptSpec::new("", "output", "", "", HasArg::No, Occur::Optional)];
+        let constraints = [Constraint::Requires(opt("color"), opt("output"))];
+        let with_fallback = |value: &str| {
+            let mut matches = parse(&specs, &[]);
+            matches.fallbacks = vec![Fallback {
+                spec: specs[0].clone(),
+                values: vec![Some(value.to_owned())],
+                sources: vec![Source::Env("MYTOOL_COLOR".to_owned())],
+            }];
+            matches
+        };
+
+        let mut matches = with_fallback("false");
+        check(&constraints, &mut matches).unwrap();
+        assert!(!matches.opt_present("color"));
+        assert_eq!(0, matches.opt_count("color"));
+        assert!(!matches.opt_bool("color"));
+
+        assert_eq!(
+            bad_usage!("Option --color requires option --output"),
+            check(&constraints, &mut with_fallback("true")).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let specs = specs();
+        let constraints = [Constraint::Conflicts(opt("json"), opt("yaml"))];
+
+        check(&constraints, &mut parse(&specs, &["--json"])).unwrap();
+        check(&constraints, &mut parse(&specs, &["--yaml"])).unwrap();
+        assert_eq!(
+            bad_usage!("Option --json conflicts with option --yaml"),
+            check(&constraints, &mut parse(&specs, &["--yaml", "--json"])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        let specs = specs();
+        let mut arguments = Arguments::default();
+        arguments.optional_positional("input", "-", "irrelevant");
+        arguments.trailing("file", 0, usize::MAX, "irrelevant");
+        let constraints = [
+            Constraint::Requires(arg("input"), opt("json")),
+            Constraint::Conflicts(opt("yaml"), arg("file")),
+            Constraint::RequiresTrailing(opt("text"), "file", 1, 1),
+        ];
+
+        check(&constraints, &mut parse_with_args(&specs, &arguments, &[])).unwrap();
+        check(&constraints, &mut parse_with_args(&specs, &arguments, &["--json", "a"])).unwrap();
+        check(&constraints, &mut parse_with_args(&specs, &arguments, &["--yaml"])).unwrap();
+        check(
+            &constraints,
+            &mut parse_with_args(&specs, &arguments, &["--text", "--json", "a", "b"]),
+        )
+        .unwrap();
+        assert_eq!(
+            bad_usage!("Argument `input` requires option --json"),
+            check(&constraints, &mut parse_with_args(&specs, &arguments, &["a"])).unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Option --text requires exactly 1 `file` argument"),
+            check(&constraints, &mut parse_with_args(&specs, &arguments, &["--text"])).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_all_violations() {
+        let specs = specs();
+        let constraints = [
+            Constraint::Requires(opt("json"), opt("text")),
+            Constraint::Exclusive(opt_refs(&["json", "yaml"])),
+            Constraint::Conflicts(opt("yaml"), opt("json")),
+        ];
+
+        assert_eq!(
+            bad_usage!(
+                "Option --json requires option --text; Options --json and --yaml are mutually \
+                 exclusive; Option --yaml conflicts with option --json"
+            ),
+            check(&constraints, &mut parse(&specs, &["--json", "--yaml"])).unwrap_err()
+        );
+    }
+}
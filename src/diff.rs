@@ -0,0 +1,206 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Line-based differences between texts in the unified format.
+
+use std::cmp;
+
+/// Number of unchanged lines to show around every change.
+const CONTEXT: usize = 3;
+
+/// A single step to transform one text into another.
+#[derive(Debug, PartialEq)]
+enum Edit<'a> {
+    /// The line is present in both texts.
+    Same(&'a str),
+
+    /// The line is only present in the old text.
+    Delete(&'a str),
+
+    /// The line is only present in the new text.
+    Insert(&'a str),
+}
+
+/// Computes the shortest sequence of edits that transforms the `old` lines into the `new` lines.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    // lcs[i][j] holds the length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            edits.push(Edit::Delete(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Insert(new[j]));
+            j += 1;
+        }
+    }
+    edits.extend(old[i..].iter().map(|line| Edit::Delete(line)));
+    edits.extend(new[j..].iter().map(|line| Edit::Insert(line)));
+    edits
+}
+
+/// Formats the range of a hunk that covers `count` lines after the first `before` lines.
+fn range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
+
+/// Appends `line` to `output` prefixed by `marker`, noting if the line lacks a newline.
+fn push_line(output: &mut String, marker: char, line: &str) {
+    output.push(marker);
+    output.push_str(line);
+    if !line.ends_with('\n') {
+        output.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Formats the differences between the `old` and `new` texts as a unified diff with headers that
+/// name the texts `old_name` and `new_name`.  Returns an empty string if the texts are equal.
+pub(crate) fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let edits = edits(&old_lines, &new_lines);
+
+    // Number of old and new lines that precede every edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in &edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Same(_) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete(_) => old_pos += 1,
+            Edit::Insert(_) => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let changes = (0..edits.len()).filter(|k| !matches!(edits[*k], Edit::Same(_)));
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for k in changes {
+        let start = k.saturating_sub(CONTEXT);
+        let end = cmp::min(k + 1 + CONTEXT, edits.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+        for edit in &edits[start..end] {
+            match edit {
+                Edit::Same(line) => push_line(&mut output, ' ', line),
+                Edit::Delete(line) => push_line(&mut output, '-', line),
+                Edit::Insert(line) => push_line(&mut output, '+', line),
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits() {
+        assert_eq!(
+            vec![
+                Edit::Same("a"),
+                Edit::Delete("b"),
+                Edit::Insert("x"),
+                Edit::Same("c"),
+                Edit::Insert("d")
+            ],
+            edits(&["a", "b", "c"], &["a", "x", "c", "d"])
+        );
+    }
+
+    #[test]
+    fn test_unified_equal() {
+        assert_eq!("", unified("", "", "old", "new"));
+        assert_eq!("", unified("a\nb\n", "a\nb\n", "old", "new"));
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n11\n12\n14\n15\n16\n";
+        assert_eq!(
+            "--- old
++++ new
+@@ -1,7 +1,7 @@
+ 1
+ 2
+ 3
+-4
++four
+ 5
+ 6
+ 7
+@@ -10,6 +10,6 @@
+ 10
+ 11
+ 12
+-13
+ 14
+ 15
++16
+",
+            unified(old, new, "old", "new")
+        );
+    }
+
+    #[test]
+    fn test_unified_empty_and_single_lines() {
+        assert_eq!("--- old\n+++ new\n@@ -0,0 +1 @@\n+a\n", unified("", "a\n", "old", "new"));
+        assert_eq!("--- old\n+++ new\n@@ -1 +0,0 @@\n-a\n", unified("a\n", "", "old", "new"));
+    }
+
+    #[test]
+    fn test_unified_missing_newline() {
+        assert_eq!(
+            "--- old\n+++ new\n@@ -1 +1 @@\n-a\n+a\n\\ No newline at end of file\n",
+            unified("a\n", "a", "old", "new")
+        );
+    }
+}
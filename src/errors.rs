@@ -12,6 +12,7 @@
 
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
 /// Errors caused by the user's invocation of the program.
 #[derive(Debug, PartialEq)]
@@ -41,3 +42,13 @@ macro_rules! __bad_usage__ {
 }
 
 pub use __bad_usage__ as bad_usage;
+
+/// Parses the `value` of `what` (such as an argument or an option) into a `T`, turning any
+/// failure into a `UsageError` that names both.
+pub(crate) fn parse_value<T>(what: &str, value: &str) -> Result<T, UsageError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|e| bad_usage!("Invalid value `{}` for {}: {}", value, what, e))
+}
@@ -0,0 +1,485 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Option values that come from sources other than the command line.
+
+use crate::config::ConfigFile;
+use crate::errors::{UsageError, bad_usage};
+use crate::{Matches, OptSpec};
+use getopts::{HasArg, Occur};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Names of the options that cannot be given in configuration files.
+const RESERVED: &[&str] = &["config", "help", "version"];
+
+/// Source of option values that were not given in the command line.
+#[derive(Clone, Debug)]
+pub(crate) enum Source {
+    /// The values came from the environment variable with the given name.
+    Env(String),
+
+    /// The values came from the given line of the configuration file at the given path.
+    File(PathBuf, usize),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(var) => write!(f, "environment variable {}", var),
+            Self::File(path, line) => write!(f, "{}:{}", path.display(), line),
+        }
+    }
+}
+
+/// Values of an option that was not given in the command line.
+#[derive(Debug)]
+pub(crate) struct Fallback {
+    /// Specification of the option that these values belong to.
+    pub(crate) spec: OptSpec,
+
+    /// The values of the option, one per occurrence.  Occurrences without a value, such as the
+    /// ones of flags, are represented as `None`.
+    pub(crate) values: Vec<Option<String>>,
+
+    /// Where each of the `values` came from, in the same order.
+    pub(crate) sources: Vec<Source>,
+}
+
+impl Fallback {
+    /// Returns true if these values turn off a boolean option, in which case the option must be
+    /// treated as absent.
+    pub(crate) fn is_disabled(&self) -> bool {
+        self.spec.bool_default.is_some()
+            && self.values.iter().all(|v| v.as_deref() == Some("false"))
+    }
+}
+
+/// Parses a textual boolean `value`.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "" | "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Computes the value of one occurrence of the option `spec` from the raw `value` that came from
+/// `source`.
+///
+/// Returns `None` if the value represents a disabled flag.  Boolean options keep their value as
+/// `true` or `false` so that it can override their default.
+fn to_value(
+    spec: &OptSpec,
+    value: String,
+    source: &Source,
+) -> Result<Option<Option<String>>, UsageError> {
+    match spec.hasarg {
+        HasArg::No => match (parse_bool(&value), spec.negated_name()) {
+            (Some(value), Some(_)) => Ok(Some(Some(value.to_string()))),
+            (Some(true), None) => Ok(Some(None)),
+            (Some(false), None) => Ok(None),
+            (None, _) => Err(bad_usage!(
+                "Invalid boolean value `{}` in {} for option {}",
+                value,
+                source,
+                spec.display_name()
+            )),
+        },
+        HasArg::Maybe if value.is_empty() => Ok(Some(None)),
+        HasArg::Maybe | HasArg::Yes => Ok(Some(Some(value))),
+    }
+}
+
+/// Values of an option along with their sources.
+type Values = (Vec<Option<String>>, Vec<Source>);
+
+/// Computes the values of the option `spec` from its environment variable, which is queried via
+/// `lookup`.
+///
+/// Returns `None` if the option has no environment variable or if the variable is not set.
+fn env_values<L>(spec: &OptSpec, lookup: &L) -> Result<Option<Values>, UsageError>
+where
+    L: Fn(&str) -> Option<OsString>,
+{
+    let var = match spec.env.as_ref() {
+        Some(var) => var,
+        None => return Ok(None),
+    };
+    let value = match lookup(var) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let source = Source::Env(var.clone());
+    let value = match value.into_string() {
+        Ok(value) => value,
+        Err(_) => return Err(bad_usage!("Invalid UTF-8 value in {}", source)),
+    };
+    match to_value(spec, value, &source)? {
+        Some(value) => Ok(Some((vec![value], vec![source]))),
+        None => Ok(Some((vec![], vec![]))),
+    }
+}
+
+/// Computes the values of the option `spec` from the last of the `files` that sets it.
+///
+/// Returns `None` if none of the files set the option.
+fn file_values(spec: &OptSpec, files: &[ConfigFile]) -> Result<Option<Values>, UsageError> {
+    for file in files.iter().rev() {
+        let mut values = None;
+        for entry in file.entries.iter().filter(|entry| spec.is(&entry.name)) {
+            let source = Source::File(file.path.clone(), entry.line);
+            let (values, sources) = match values.as_mut() {
+                None => values.insert((vec![], vec![])),
+                Some(_) if spec.occur != Occur::Multi => {
+                    return Err(bad_usage!(
+                        "Option {} given more than once in {}",
+                        spec.display_name(),
+                        source
+                    ));
+                }
+                Some(values) => values,
+            };
+            if let Some(value) = to_value(spec, entry.value.clone(), &source)? {
+                values.push(value);
+                sources.push(source);
+            }
+        }
+        if values.is_some() {
+            return Ok(values);
+        }
+    }
+    Ok(None)
+}
+
+/// Computes the values of the options in `specs` that are not present in `matches`.
+///
+/// The values come from the environment variables attached to the options, which are queried
+/// via `lookup`, or from the configuration `files`, which are sorted by increasing precedence.
+///
+/// Returns an error if the files refer to unknown options in the section of the current command
+/// or if a required option has no value in any of these places.  Settings for unknown options
+/// inherited from enclosing sections are ignored because they may belong to other commands.
+pub(crate) fn resolve<'a, I, L>(
+    specs: I,
+    matches: &Matches,
+    lookup: L,
+    files: &[ConfigFile],
+) -> Result<Vec<Fallback>, UsageError>
+where
+    I: IntoIterator<Item = &'a OptSpec>,
+    L: Fn(&str) -> Option<OsString>,
+{
+    let specs = specs.into_iter().collect::<Vec<&OptSpec>>();
+
+    for file in files {
+        for entry in &file.entries {
+            let known = specs.iter().any(|s| s.is(&entry.name));
+            if RESERVED.contains(&entry.name.as_str()) || (entry.scoped && !known) {
+                return Err(bad_usage!(
+                    "Unknown option `{}` in {}",
+                    entry.name,
+                    Source::File(file.path.clone(), entry.line)
+                ));
+            }
+        }
+    }
+
+    let mut fallbacks = vec![];
+    for spec in specs {
+        if matches.opt_present(spec.name())
+            || spec.negated_name().is_some_and(|name| matches.opt_present(&name))
+        {
+            continue;
+        }
+        let values = match env_values(spec, &lookup)? {
+            Some(values) => Some(values),
+            None => file_values(spec, files)?,
+        };
+        match values {
+            Some((values, _sources)) if values.is_empty() => (),
+            Some((values, sources)) => {
+                fallbacks.push(Fallback { spec: spec.clone(), values, sources })
+            }
+            None if spec.occur == Occur::Req => {
+                return Err(bad_usage!("Required option '{}' missing", spec.name()));
+            }
+            None => (),
+        }
+    }
+    Ok(fallbacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Entry;
+    use crate::test_helpers::parse_opts;
+
+    /// Parses `args` with `specs` and computes the fallbacks from the environment in `env` and
+    /// the configuration `files`.
+    fn fallbacks(
+        specs: &[OptSpec],
+        args: &[&str],
+        env: &[(&str, &str)],
+        files: &[ConfigFile],
+    ) -> Result<Vec<Fallback>, UsageError> {
+        let matches = parse_opts(specs, args);
+        resolve(
+            specs,
+            &matches,
+            |var| env.iter().find(|(k, _v)| *k == var).map(|(_k, v)| OsString::from(v)),
+            files,
+        )
+    }
+
+    /// Creates a configuration file at `path` with one `settings` entry per line, all under the
+    /// section of the current command.
+    fn config_file(path: &str, settings: &[(&str, &str)]) -> ConfigFile {
+        ConfigFile {
+            path: PathBuf::from(path),
+            entries: settings
+                .iter()
+                .enumerate()
+                .map(|(i, (name, value))| Entry {
+                    line: i + 1,
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    scoped: true,
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates an option specification with an environment variable.
+    fn spec(long_name: &str, hasarg: HasArg, var: &str) -> OptSpec {
+        let mut spec = OptSpec::new("", long_name, "", "", hasarg, Occur::Optional);
+        spec.env = Some(var.to_owned());
+        spec
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        for value in ["1", "true", "TRUE", "yes", "on"] {
+            assert_eq!(Some(true), parse_bool(value));
+        }
+        for value in ["", "0", "false", "No", "off"] {
+            assert_eq!(Some(false), parse_bool(value));
+        }
+        assert_eq!(None, parse_bool("2"));
+    }
+
+    #[test]
+    fn test_resolve_env_values() {
+        let specs = [
+            spec("flag", HasArg::No, "FLAG"),
+            spec("maybe", HasArg::Maybe, "MAYBE"),
+            spec("value", HasArg::Yes, "VALUE"),
+        ];
+        let fallbacks =
+            fallbacks(&specs, &[], &[("FLAG", "yes"), ("MAYBE", ""), ("VALUE", "abc")], &[])
+                .unwrap();
+        assert_eq!(3, fallbacks.len());
+        assert!(fallbacks[0].spec.is("flag"));
+        assert_eq!(vec![None], fallbacks[0].values);
+        assert!(fallbacks[1].spec.is("maybe"));
+        assert_eq!(vec![None], fallbacks[1].values);
+        assert!(fallbacks[2].spec.is("value"));
+        assert_eq!(vec![Some("abc".to_owned())], fallbacks[2].values);
+        assert_eq!("environment variable VALUE", fallbacks[2].sources[0].to_string());
+    }
+
+    #[test]
+    fn test_resolve_env_disabled_flag() {
+        let specs = [spec("flag", HasArg::No, "FLAG")];
+        assert!(fallbacks(&specs, &[], &[("FLAG", "false")], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_env_bool() {
+        let mut spec = spec("color", HasArg::No, "COLOR");
+        spec.bool_default = Some(true);
+        let specs = [spec];
+
+        let values = |args: &[&str], value: &str| {
+            fallbacks(&specs, args, &[("COLOR", value)], &[])
+                .unwrap()
+                .into_iter()
+                .map(|fallback| fallback.values)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![vec![Some("false".to_owned())]], values(&[], "no"));
+        assert_eq!(vec![vec![Some("true".to_owned())]], values(&[], "yes"));
+        assert!(values(&["--no-color"], "yes").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_env_unset() {
+        let specs = [spec("flag", HasArg::No, "FLAG"), spec("value", HasArg::Yes, "VALUE")];
+        assert!(fallbacks(&specs, &[], &[], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_env_required() {
+        let mut specs = [spec("value", HasArg::Yes, "VALUE")];
+        specs[0].occur = Occur::Req;
+        assert_eq!(1, fallbacks(&specs, &[], &[("VALUE", "x")], &[]).unwrap().len());
+        assert_eq!(
+            bad_usage!("Required option 'value' missing"),
+            fallbacks(&specs, &[], &[], &[]).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_command_line_wins() {
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        assert!(fallbacks(&specs, &["--value=x"], &[("VALUE", "y")], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_env_invalid_bool() {
+        let specs = [spec("flag", HasArg::No, "FLAG")];
+        assert_eq!(
+            bad_usage!(
+                "Invalid boolean value `foo` in environment variable FLAG for option --flag"
+            ),
+            fallbacks(&specs, &[], &[("FLAG", "foo")], &[]).unwrap_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_env_invalid_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        let matches = parse_opts(&specs, &[]);
+        assert_eq!(
+            bad_usage!("Invalid UTF-8 value in environment variable VALUE"),
+            resolve(&specs, &matches, |_var| Some(OsString::from_vec(vec![0xff])), &[])
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_files_precedence() {
+        let specs = [spec("first", HasArg::Yes, "FIRST"), spec("second", HasArg::Yes, "SECOND")];
+        let files = [
+            config_file("system", &[("first", "1"), ("second", "2")]),
+            config_file("user", &[("second", "3")]),
+        ];
+        let fallbacks = fallbacks(&specs, &[], &[], &files).unwrap();
+        assert_eq!(2, fallbacks.len());
+        assert_eq!(vec![Some("1".to_owned())], fallbacks[0].values);
+        assert_eq!(vec![Some("3".to_owned())], fallbacks[1].values);
+    }
+
+    #[test]
+    fn test_resolve_files_env_wins() {
+        let specs = [spec("flag", HasArg::No, "FLAG"), spec("value", HasArg::Yes, "VALUE")];
+        let files = [config_file("file", &[("flag", "yes"), ("value", "file")])];
+        let fallbacks =
+            fallbacks(&specs, &[], &[("FLAG", "no"), ("VALUE", "env")], &files).unwrap();
+        assert_eq!(1, fallbacks.len());
+        assert!(fallbacks[0].spec.is("value"));
+        assert_eq!(vec![Some("env".to_owned())], fallbacks[0].values);
+    }
+
+    #[test]
+    fn test_resolve_files_command_line_wins() {
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        let files = [config_file("file", &[("value", "file")])];
+        assert!(fallbacks(&specs, &["--value=x"], &[], &files).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_files_multi() {
+        let mut specs = [spec("flag", HasArg::No, "FLAG"), spec("value", HasArg::Yes, "VALUE")];
+        specs[0].occur = Occur::Multi;
+        specs[1].occur = Occur::Multi;
+        let files = [config_file(
+            "file",
+            &[("value", "a"), ("flag", "yes"), ("value", "b"), ("flag", "no"), ("flag", "1")],
+        )];
+        let fallbacks = fallbacks(&specs, &[], &[], &files).unwrap();
+        assert_eq!(2, fallbacks.len());
+        assert_eq!(vec![None, None], fallbacks[0].values);
+        assert_eq!(
+            vec!["file:2", "file:5"],
+            fallbacks[0].sources.iter().map(Source::to_string).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![Some("a".to_owned()), Some("b".to_owned())], fallbacks[1].values);
+        assert_eq!(
+            vec!["file:1", "file:3"],
+            fallbacks[1].sources.iter().map(Source::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_resolve_files_required() {
+        let mut specs = [spec("value", HasArg::Yes, "VALUE")];
+        specs[0].occur = Occur::Req;
+        let files = [config_file("file", &[("value", "x")])];
+        assert_eq!(1, fallbacks(&specs, &[], &[], &files).unwrap().len());
+    }
+
+    #[test]
+    fn test_resolve_files_duplicate() {
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        let files = [config_file("some/file", &[("value", "a"), ("value", "b")])];
+        assert_eq!(
+            bad_usage!("Option --value given more than once in some/file:2"),
+            fallbacks(&specs, &[], &[], &files).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_files_unknown_option() {
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        let files = [config_file("some/file", &[("value", "a"), ("other", "b")])];
+        assert_eq!(
+            bad_usage!("Unknown option `other` in some/file:2"),
+            fallbacks(&specs, &[], &[], &files).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_files_unknown_option_inherited() {
+        let specs = [spec("value", HasArg::Yes, "VALUE")];
+        let mut file = config_file("some/file", &[("value", "a"), ("other", "b")]);
+        for entry in &mut file.entries {
+            entry.scoped = false;
+        }
+        let fallbacks = fallbacks(&specs, &[], &[], &[file]).unwrap();
+        assert_eq!(1, fallbacks.len());
+        assert_eq!(vec![Some("a".to_owned())], fallbacks[0].values);
+    }
+
+    #[test]
+    fn test_resolve_files_reserved_option() {
+        let specs = [spec("config", HasArg::Yes, "CONFIG")];
+        let files = [config_file("some/file", &[("config", "a")])];
+        assert_eq!(
+            bad_usage!("Unknown option `config` in some/file:1"),
+            fallbacks(&specs, &[], &[], &files).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_files_invalid_bool() {
+        let specs = [spec("flag", HasArg::No, "FLAG")];
+        let files = [config_file("some/file", &[("flag", "foo")])];
+        assert_eq!(
+            bad_usage!("Invalid boolean value `foo` in some/file:1 for option --flag"),
+            fallbacks(&specs, &[], &[], &files).unwrap_err()
+        );
+    }
+}
@@ -39,26 +39,64 @@
 #![warn(unused, unused_extern_crates, unused_import_braces, unused_qualifications)]
 #![warn(unsafe_code)]
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use getopts::Matches as OptionMatches;
-use getopts::Options;
 use std::env;
-use std::io;
+use std::ffi::OsString;
+use std::io::{self, Write as _};
+use std::mem;
+use std::path::PathBuf;
 
 mod args;
 use args::{Arguments, Matches as ArgumentMatches};
 
+pub mod console;
+
+mod constraints;
+use constraints::Constraint;
+
+mod config;
+use config::Config;
+
+mod diff;
+
 mod errors;
-use errors::UsageError;
+pub use errors::UsageError;
+
+mod fallbacks;
+use fallbacks::Fallback;
 
 mod licenses;
 use licenses::License;
 
+mod lists;
+
+mod maps;
+pub use maps::DuplicateKeys;
+
 mod opts;
+use opts::OptSpec;
+
+mod os_args;
 
 pub mod prelude;
 
+mod response_files;
+
 mod run;
+use run::Parsed;
+
+mod subcommands;
+use subcommands::{Inherited, Main, Subcommand};
+
+#[cfg(test)]
+mod test_helpers;
+
+mod units;
+
+mod validators;
+
+mod verbosity;
 
 pub mod testutils;
 
@@ -68,24 +106,97 @@ pub struct Matches {
     /// The program name.
     pub program_name: String,
 
-    /// The option matches.
-    opts: OptionMatches,
+    /// The option matches of every command in the command line, from the outermost, along with
+    /// the offset to apply to their option positions.
+    opts: Vec<(usize, OptionMatches)>,
 
     /// The argument matches.
     args: ArgumentMatches,
+
+    /// Values for options that were not given in the command line.
+    fallbacks: Vec<Fallback>,
+
+    /// Specifications of all options accepted by the command.
+    specs: Vec<OptSpec>,
+
+    /// Whether the values that came from the command line are escaped by `os_args::escape`.
+    escaped: bool,
+}
+
+/// Result of processing the command line with `Builder::parse`.
+#[derive(Debug)]
+pub enum Outcome {
+    /// Help was requested.  Contains the help message to print.
+    Help(String),
+
+    /// The version was requested.  Contains the version information to print.
+    Version(String),
+
+    /// The command line was valid.  Contains the results of options and arguments parsing.
+    Matches(Matches),
+
+    /// The command line selected a subcommand.  Contains the name of the subcommand and the
+    /// outcome of processing its part of the command line.
+    Subcommand {
+        /// Name of the selected subcommand.
+        name: &'static str,
+
+        /// Outcome of processing the command line of the subcommand.
+        outcome: Box<Outcome>,
+    },
+
+    /// The command line selected an external subcommand, which the caller is responsible for
+    /// running.
+    External {
+        /// Path to the executable of the external subcommand.
+        path: PathBuf,
+
+        /// Arguments to pass to the external subcommand.
+        args: Vec<OsString>,
+    },
+}
+
+impl Outcome {
+    /// Returns the outcome of the innermost command selected by the command line.
+    fn innermost(self) -> Outcome {
+        match self {
+            Outcome::Subcommand { outcome, .. } => outcome.innermost(),
+            outcome => outcome,
+        }
+    }
+}
+
+/// Command selected by the command line, which is the innermost subcommand if there is any.
+struct Selected {
+    /// Metadata of the selected command.
+    app: App,
+
+    /// Entry point of the selected subcommand, or `None` if the command line did not select any.
+    main: Option<Main<'static>>,
+
+    /// Outcome of processing the whole command line.
+    outcome: Outcome,
 }
 
 /// Container for the metadata about the user-defined application.
+#[derive(Clone)]
 struct App {
     stylized_name: &'static str,
     version: &'static str,
     program_name: String,
+    command: String,
     copyright: Option<&'static str>,
     license: Option<License>,
     manpage: Option<(&'static str, &'static str)>,
     homepage: Option<&'static str>,
     bugs: Option<&'static str>,
     extra_help: Option<fn(&mut dyn io::Write) -> io::Result<()>>,
+    external_subcommands: bool,
+    multicall: bool,
+    config: Option<Config>,
+    response_files: bool,
+    verbosity_flags: bool,
+    os_args: bool,
     #[cfg(feature = "env_logger")]
     init_env_logger: bool,
 }
@@ -94,9 +205,12 @@ struct App {
 #[must_use]
 pub struct Builder {
     app: App,
-    env_args: env::Args,
-    opts: Options,
+    env_args: Vec<String>,
+    opts: Vec<OptSpec>,
     args: Arguments,
+    constraints: Vec<Constraint>,
+    subcommands: Vec<Subcommand>,
+    inherited: Inherited,
 }
 
 impl Builder {
@@ -112,19 +226,53 @@ impl Builder {
     /// `copyright` is a user-provided string that must start with `Copyright` and that provides an
     /// informational copyright message for the user.
     ///
-    /// `args` should always be `env::args()`.
+    /// `args` should always be `env::args()`, possibly with some of its items already consumed.
+    /// The remaining arguments are read from `env::args_os()` instead so that arguments that are
+    /// not valid UTF-8 cause a usage error instead of a panic.  Use `new_os` to accept such
+    /// arguments.
     pub fn new(stylized_name: &'static str, version: &'static str, env_args: env::Args) -> Self {
-        let (program_name, env_args) = run::program_name(env_args, stylized_name.to_lowercase());
+        let args_os = env::args_os();
+        let consumed = args_os.len().saturating_sub(env_args.len());
+        let mut builder = Self::from_args(stylized_name, version, args_os.skip(consumed));
+        builder.app.os_args = false;
+        builder
+    }
 
-        let mut opts = Options::new();
-        opts.optflag("h", "help", "show command-line usage information and exit");
-        opts.optflag("", "version", "show version information and exit");
+    /// Same as `new` but accepts arguments that are not valid UTF-8, such as arbitrary file
+    /// names on Unix systems.
+    ///
+    /// The values of such arguments can be queried losslessly with the `Matches::arg_pos_os`,
+    /// `Matches::arg_trail_os` and `Matches::opt_str_os` accessors.  The `String`-based accessors
+    /// return them with their invalid sequences escaped as private use characters.
+    ///
+    /// `args` should always be `env::args_os()`.
+    pub fn new_os(
+        stylized_name: &'static str,
+        version: &'static str,
+        env_args: env::ArgsOs,
+    ) -> Self {
+        Self::from_args(stylized_name, version, env_args)
+    }
+
+    /// Same as `new_os` but takes the command line from any source, which is useful to test the
+    /// setup of an application or to parse commands that do not come from the process, such as
+    /// the ones typed in a REPL.
+    ///
+    /// The first of the `args` is the program name, as in `env::args_os()`.
+    pub fn from_args<I>(stylized_name: &'static str, version: &'static str, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let (program_name, env_args) =
+            run::program_name(args.into_iter().map(Into::into), stylized_name.to_lowercase());
 
         let license = License::from_cargo();
 
         let app = App {
             stylized_name,
             version,
+            command: program_name.clone(),
             program_name,
             copyright: None,
             license,
@@ -132,11 +280,36 @@ impl Builder {
             homepage: None,
             bugs: None,
             extra_help: None,
+            external_subcommands: false,
+            multicall: false,
+            config: None,
+            response_files: false,
+            verbosity_flags: false,
+            os_args: true,
             #[cfg(feature = "env_logger")]
             init_env_logger: true,
         };
 
-        Self { app, env_args, opts, args: Arguments::default() }
+        Self::with_app(app, env_args.map(os_args::escape).collect(), Inherited::default()).optflag(
+            "",
+            "version",
+            "show version information and exit",
+        )
+    }
+
+    /// Creates a new builder for `app` that will process `env_args` in the context of the
+    /// `inherited` state from the parent commands, if any.
+    fn with_app(app: App, env_args: Vec<String>, inherited: Inherited) -> Self {
+        Self {
+            app,
+            env_args,
+            opts: vec![],
+            args: Arguments::default(),
+            constraints: vec![],
+            subcommands: vec![],
+            inherited,
+        }
+        .optflag("h", "help", "show command-line usage information and exit")
     }
 
     /// Sets the bug reporting URL of the application to `bugs`.
@@ -198,32 +371,160 @@ impl Builder {
     /// only to let you implement a completely imperative program without any flow control
     /// redirections.
     pub fn start(self) -> Result<Option<Matches>> {
-        run::pre_run(&self.app, self.opts, self.args, self.env_args)
+        let selected = self.select(false, Some(&mut io::stdin())).map_err(|err| err.1)?;
+        match selected.outcome {
+            Outcome::Help(text) | Outcome::Version(text) => {
+                let _ = write!(console::stdout(), "{}", text);
+                Ok(None)
+            }
+            Outcome::Matches(matches) => {
+                #[cfg(feature = "env_logger")]
+                run::init_logger(&selected.app, &matches);
+                Ok(Some(matches))
+            }
+            Outcome::Subcommand { name, .. } => {
+                Err(anyhow!("Subcommand `{}` can only be executed by run or run_async", name))
+            }
+            Outcome::External { path, .. } => Err(anyhow!(
+                "External subcommand {} can only be executed by run or run_async",
+                path.display()
+            )),
+        }
+    }
+
+    /// Processes arguments as previously configured without producing any output.
+    ///
+    /// Returns the text to print if one of the standard options like `--help` or `--version` was
+    /// given, or the results of the argument parsing otherwise.  If the command line selects a
+    /// subcommand, returns the outcome of processing the arguments of the subcommand nested in
+    /// an `Outcome::Subcommand`.  Usage errors are returned as `UsageError`s.
+    ///
+    /// This is the building block of `start`, `run` and `run_async` and is useful to embed an
+    /// application in another program or to test it in-process.  Unlike them, this has no side
+    /// effects on the process: it does not initialize the logger, does not run external
+    /// subcommands, and does not read response files from stdin, which is reported as an error
+    /// instead.
+    pub fn parse(self) -> Result<Outcome> {
+        self.select(false, None).map(|selected| selected.outcome).map_err(|err| err.1)
+    }
+
+    /// Processes the command line of this command, reading response files from `stdin` if
+    /// available.
+    fn pre_run(&mut self, stdin: Option<&mut dyn io::Read>) -> Result<Parsed> {
+        run::pre_run(
+            &self.app,
+            mem::take(&mut self.opts),
+            mem::take(&mut self.args),
+            mem::take(&mut self.constraints),
+            &self.subcommands,
+            mem::take(&mut self.inherited),
+            mem::take(&mut self.env_args),
+            stdin,
+        )
+    }
+
+    /// Processes the command line, descending into the selected subcommands, and returns the
+    /// innermost selected command.  If `sync` is true, selecting an async subcommand is an error.
+    ///
+    /// Errors come along with the metadata of the command that detected them so that they can
+    /// be reported in terms of that command.
+    fn select(
+        mut self,
+        sync: bool,
+        stdin: Option<&mut dyn io::Read>,
+    ) -> Result<Selected, Box<(App, anyhow::Error)>> {
+        let parsed = match self.pre_run(stdin) {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(Box::new((self.app, e))),
+        };
+        let outcome = match parsed {
+            Parsed::Help(text) => Outcome::Help(text),
+            Parsed::Version(text) => Outcome::Version(text),
+            Parsed::Main(matches) => Outcome::Matches(matches),
+            Parsed::Subcommand { index, command, env_args, inherited } => {
+                let subcommand = self.subcommands.swap_remove(index);
+                if sync && let Main::Async(_) = subcommand.main {
+                    let e =
+                        anyhow!("Subcommand `{}` is async and requires run_async", subcommand.name);
+                    return Err(Box::new((self.app, e)));
+                }
+                let builder = subcommand.builder(&self.app, command, env_args, inherited);
+                let mut selected = builder.select(sync, None)?;
+                selected.main = selected.main.or(Some(subcommand.main));
+                selected.outcome = Outcome::Subcommand {
+                    name: subcommand.name,
+                    outcome: Box::new(selected.outcome),
+                };
+                return Ok(selected);
+            }
+            Parsed::External(path, env_args) => {
+                Outcome::External { path, args: os_args::to_os(env_args, self.app.os_args) }
+            }
+        };
+        Ok(Selected { app: self.app, main: None, outcome })
+    }
+
+    /// Takes care of the innermost `outcome` of the command described by `app` other than running
+    /// its main function.  Returns the matches to run the main function with, or the exit code
+    /// if there is nothing else to do.
+    fn finish(app: &App, outcome: Outcome) -> Result<Matches, i32> {
+        match outcome.innermost() {
+            Outcome::Help(text) | Outcome::Version(text) => {
+                let _ = write!(console::stdout(), "{}", text);
+                Err(0)
+            }
+            Outcome::Matches(matches) => {
+                #[cfg(feature = "env_logger")]
+                run::init_logger(app, &matches);
+                Ok(matches)
+            }
+            Outcome::External { path, args } => match subcommands::run_external(&path, args) {
+                Ok(code) => Err(code),
+                Err(e) => Err(run::handle_error(app, e)),
+            },
+            Outcome::Subcommand { .. } => unreachable!("The innermost outcome is not a subcommand"),
+        }
     }
 
-    /// Starts the application delegating execution to `main`.
+    /// Starts the application delegating execution to `main` or to the selected subcommand.
     ///
     /// Returns the exit code that the caller must propagate to the caller via `process::exit`.
     pub fn run(self, main: fn(Matches) -> Result<i32>) -> i32 {
-        match run::pre_run(&self.app, self.opts, self.args, self.env_args) {
-            Ok(None) => 0,
-            Ok(Some(matches)) => match main(matches) {
-                Ok(code) => code,
-                Err(e) => run::handle_error(&self.app, e),
-            },
-            Err(e) => run::handle_error(&self.app, e),
+        let selected = match self.select(true, Some(&mut io::stdin())) {
+            Ok(selected) => selected,
+            Err(err) => return run::handle_error(&err.0, err.1),
+        };
+        let matches = match Self::finish(&selected.app, selected.outcome) {
+            Ok(matches) => matches,
+            Err(code) => return code,
+        };
+        let result = match selected.main {
+            Some(main) => main.call_sync(matches),
+            None => main(matches),
+        };
+        match result {
+            Ok(code) => code,
+            Err(e) => run::handle_error(&selected.app, e),
         }
     }
 
     /// Async version of `run`.
     pub async fn run_async<F: Future<Output = Result<i32>>>(self, main: fn(Matches) -> F) -> i32 {
-        match run::pre_run(&self.app, self.opts, self.args, self.env_args) {
-            Ok(None) => 0,
-            Ok(Some(matches)) => match main(matches).await {
-                Ok(code) => code,
-                Err(e) => run::handle_error(&self.app, e),
-            },
-            Err(e) => run::handle_error(&self.app, e),
+        let selected = match self.select(false, Some(&mut io::stdin())) {
+            Ok(selected) => selected,
+            Err(err) => return run::handle_error(&err.0, err.1),
+        };
+        let matches = match Self::finish(&selected.app, selected.outcome) {
+            Ok(matches) => matches,
+            Err(code) => return code,
+        };
+        let result = match selected.main {
+            Some(main) => main.call(matches).await,
+            None => main(matches).await,
+        };
+        match result {
+            Ok(code) => code,
+            Err(e) => run::handle_error(&selected.app, e),
         }
     }
 }
@@ -256,3 +557,169 @@ macro_rules! tokio_app {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a builder to process `args` that does not have side effects on the process.
+    fn builder(args: &[&str]) -> Builder {
+        let builder = Builder::from_args("Test", "1.0", args.iter().copied());
+        #[cfg(feature = "env_logger")]
+        let builder = builder.disable_init_env_logger();
+        builder
+    }
+
+    #[test]
+    fn test_from_args() {
+        let matches = builder(&["/usr/bin/some-program", "--flag", "arg"])
+            .optflag("", "flag", "a flag")
+            .posarg("arg", "an argument")
+            .start()
+            .unwrap()
+            .unwrap();
+        assert_eq!("some-program", matches.program_name);
+        assert!(matches.opt_present("flag"));
+        assert_eq!("arg", matches.arg_pos("arg"));
+    }
+
+    #[test]
+    fn test_from_args_no_program_name() {
+        let matches = builder(&[]).start().unwrap().unwrap();
+        assert_eq!("test", matches.program_name);
+    }
+
+    #[test]
+    fn test_parse_help() {
+        match builder(&["program", "--help"]).bugs("https://example.com/").parse().unwrap() {
+            Outcome::Help(text) => {
+                assert!(text.starts_with("Usage: program [options]\n"), "Got: {}", text);
+                assert!(text.ends_with("Report bugs to: https://example.com/\n"), "Got: {}", text);
+            }
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_parse_version() {
+        match builder(&["program", "--version"]).license(License::MIT).parse().unwrap() {
+            Outcome::Version(text) => {
+                assert_eq!("Test 1.0\nLicense MIT <https://opensource.org/license/mit>\n", text)
+            }
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_parse_matches() {
+        match builder(&["program", "arg"]).posarg("arg", "an argument").parse().unwrap() {
+            Outcome::Matches(matches) => assert_eq!("arg", matches.arg_pos("arg")),
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_parse_no_side_effects() {
+        for _ in 0..2 {
+            let err = Builder::from_args("Test", "1.0", ["program", "@-"])
+                .response_files()
+                .parse()
+                .unwrap_err();
+            assert_eq!(
+                "Cannot read response file from stdin: not available",
+                err.downcast_ref::<UsageError>().unwrap().to_string()
+            );
+
+            match Builder::from_args("Test", "1.0", ["program"]).parse().unwrap() {
+                Outcome::Matches(matches) => assert_eq!("program", matches.program_name),
+                outcome => panic!("Unexpected outcome: {:?}", outcome),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        let err = builder(&["program", "--foo"]).parse().unwrap_err();
+        assert_eq!(
+            "Unrecognized option: 'foo'",
+            err.downcast_ref::<UsageError>().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_subcommand() {
+        fn subcommand_main(_matches: Matches) -> Result<i32> {
+            Ok(0)
+        }
+        let parse = |args: &[&str]| {
+            builder(args)
+                .subcommand("sub", "", |b| b.posarg("arg", "an argument"), subcommand_main)
+                .parse()
+                .unwrap()
+        };
+
+        match parse(&["program", "--help"]) {
+            Outcome::Help(text) => assert!(text.contains("sub"), "Got: {}", text),
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+        match parse(&["program", "sub", "value"]) {
+            Outcome::Subcommand { name: "sub", outcome } => match *outcome {
+                Outcome::Matches(matches) => assert_eq!("value", matches.arg_pos("arg")),
+                outcome => panic!("Unexpected outcome: {:?}", outcome),
+            },
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+        match parse(&["program", "sub", "--help"]) {
+            Outcome::Subcommand { name: "sub", outcome } => match *outcome {
+                Outcome::Help(text) => {
+                    assert!(text.starts_with("Usage: program sub [options] arg"), "Got: {}", text)
+                }
+                outcome => panic!("Unexpected outcome: {:?}", outcome),
+            },
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_run_async_subcommand() {
+        async fn async_main(_matches: Matches) -> Result<i32> {
+            Ok(0)
+        }
+        fn sync_main(_matches: Matches) -> Result<i32> {
+            writeln!(console::stdout(), "sync")?;
+            Ok(5)
+        }
+        let run = |args: &[&str]| {
+            console::capture(|| {
+                builder(args)
+                    .subcommand("sync", "", |b| b, sync_main)
+                    .subcommand_async("async", "", |b| b, async_main)
+                    .run(|_| Ok(0))
+            })
+        };
+
+        let (code, stdout, stderr) = run(&["program", "async"]);
+        assert_eq!(1, code);
+        assert!(stdout.is_empty());
+        assert_eq!(
+            "program: Subcommand `async` is async and requires run_async\n",
+            String::from_utf8(stderr).unwrap()
+        );
+
+        let (code, stdout, stderr) = run(&["program", "sync"]);
+        assert_eq!(5, code);
+        assert_eq!("sync\n", String::from_utf8(stdout).unwrap());
+        assert!(stderr.is_empty());
+
+        let (code, stdout, stderr) = run(&["program", "--help"]);
+        assert_eq!(0, code);
+        assert!(String::from_utf8(stdout).unwrap().starts_with("Usage: program [options]"));
+        assert!(stderr.is_empty());
+    }
+
+    #[test]
+    fn test_from_args_usage_error() {
+        let err = builder(&["program", "extra"]).start().unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "Got: {}", err);
+    }
+}
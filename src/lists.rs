@@ -0,0 +1,125 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Options that take lists of values separated by a delimiter.
+
+use crate::errors::{UsageError, parse_value};
+use crate::opts::OptSpec;
+use crate::{Builder, Matches};
+use getopts::{HasArg, Occur};
+use std::fmt;
+use std::str::FromStr;
+
+/// Formats the help `hint` of a list option with elements separated by `delimiter`.
+fn list_hint(hint: &str, delimiter: char) -> String {
+    format!("{}1{}{}2{}...", hint, delimiter, hint, delimiter)
+}
+
+impl Builder {
+    /// Defines an option that takes a list of values separated by `delimiter`, such as
+    /// `--features=F1,F2`, and that can be given multiple times to extend the list.  Use
+    /// `Matches::opt_list` to query the list.
+    ///
+    /// The `hint` names a single element and is shown in the help message as `HINT1,HINT2,...`.
+    pub fn optlist(
+        mut self,
+        short_name: &str,
+        long_name: &str,
+        desc: &str,
+        hint: &str,
+        delimiter: char,
+    ) -> Self {
+        let hint = list_hint(hint, delimiter);
+        let mut spec = OptSpec::new(short_name, long_name, desc, &hint, HasArg::Yes, Occur::Multi);
+        spec.delimiter = Some(delimiter);
+        self.opts.push(spec);
+        self
+    }
+}
+
+impl Matches {
+    /// Returns the elements of the list option `name` across all of its occurrences, parsed
+    /// into a `T`.  Empty elements are ignored.
+    ///
+    /// Returns a `UsageError` that names the failing element if any element is invalid.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `name` is not a list option.
+    pub fn opt_list<T>(&self, name: &str) -> Result<Vec<T>, UsageError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let spec = self.spec(name);
+        let delimiter = match spec.delimiter {
+            Some(delimiter) => delimiter,
+            None => panic!("Option '{}' is not a list option", name),
+        };
+        let values = self.opt_strs_described(name)?;
+        let elements = values
+            .iter()
+            .flat_map(|(what, value)| value.split(delimiter).map(move |element| (what, element)));
+        elements
+            .filter(|(_what, element)| !element.is_empty())
+            .enumerate()
+            .map(|(i, (what, element))| {
+                parse_value(&format!("element {} of {}", i + 1, what), element)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::bad_usage;
+    use crate::test_helpers::parse_opts;
+
+    /// Parses `args` with a `-f/--features` list option delimited by commas.
+    fn parse(args: &[&str]) -> Matches {
+        let mut spec = OptSpec::new("f", "features", "", "F", HasArg::Yes, Occur::Multi);
+        spec.delimiter = Some(',');
+        let specs = [spec, OptSpec::new("x", "", "", "", HasArg::Yes, Occur::Multi)];
+        parse_opts(&specs, args)
+    }
+
+    #[test]
+    fn test_list_hint() {
+        assert_eq!("F1,F2,...", list_hint("F", ','));
+        assert_eq!("PATH1:PATH2:...", list_hint("PATH", ':'));
+    }
+
+    #[test]
+    fn test_matches_opt_list() {
+        assert!(parse(&[]).opt_list::<String>("features").unwrap().is_empty());
+
+        let matches = parse(&["--features=a,b", "-f", "c", "-f", ",d,,e,"]);
+        assert_eq!(vec!["a", "b", "c", "d", "e"], matches.opt_list::<String>("f").unwrap());
+    }
+
+    #[test]
+    fn test_matches_opt_list_invalid_element() {
+        let matches = parse(&["--features=1,2", "-f", "3,x,5"]);
+        assert_eq!(
+            bad_usage!(
+                "Invalid value `x` for element 4 of option --features: invalid digit found in \
+                 string"
+            ),
+            matches.opt_list::<i32>("features").unwrap_err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Option 'x' is not a list option")]
+    fn test_matches_opt_list_not_list() {
+        let _ = parse(&[]).opt_list::<String>("x");
+    }
+}
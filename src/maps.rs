@@ -0,0 +1,176 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Options that collect `key=value` pairs into maps.
+
+use crate::errors::{UsageError, bad_usage, parse_value};
+use crate::{Builder, Matches};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Explanation of why a map entry is malformed.
+const MALFORMED: &str = "must be of the form KEY=VALUE";
+
+/// Policy to apply when a map option receives the same key more than once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateKeys {
+    /// The last value given for the key wins.
+    LastWins,
+
+    /// Giving the same key more than once is a usage error.
+    Reject,
+}
+
+/// Splits a `key=value` entry into its parts, or returns `None` if it is malformed.
+fn split_entry(entry: &str) -> Option<(&str, &str)> {
+    match entry.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Some((key, value)),
+        _ => None,
+    }
+}
+
+impl Builder {
+    /// Defines an option that can be given multiple times with `KEY=VALUE` arguments, such as
+    /// `-D name=value`, to build a map.  Use `Matches::opt_map` to query the map.
+    ///
+    /// Entries without an `=` sign or with an empty key are rejected with a usage error.
+    pub fn optmap(self, short_name: &str, long_name: &str, desc: &str) -> Self {
+        let name = if long_name.is_empty() { short_name } else { long_name };
+        self.optmulti(short_name, long_name, desc, "KEY=VALUE").validate(name, |entry| {
+            match split_entry(entry) {
+                Some(_) => Ok(()),
+                None => Err(MALFORMED.to_owned()),
+            }
+        })
+    }
+}
+
+impl Matches {
+    /// Returns the `key=value` entries of the map option `name` as a map sorted by key.
+    ///
+    /// `duplicates` determines what to do if the same key is given more than once.
+    pub fn opt_map(
+        &self,
+        name: &str,
+        duplicates: DuplicateKeys,
+    ) -> Result<BTreeMap<String, String>, UsageError> {
+        self.opt_map_get(name, duplicates)
+    }
+
+    /// Same as `opt_map` but parses the values into a `T` and returns a `UsageError` that names
+    /// the option, the key and the value if any value is invalid.
+    pub fn opt_map_get<T>(
+        &self,
+        name: &str,
+        duplicates: DuplicateKeys,
+    ) -> Result<BTreeMap<String, T>, UsageError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let mut map = BTreeMap::new();
+        for (what, entry) in self.opt_strs_described(name)? {
+            let (key, value) = match split_entry(&entry) {
+                Some(parts) => parts,
+                None => {
+                    return Err(bad_usage!(
+                        "Invalid value `{}` for {}: {}",
+                        entry,
+                        what,
+                        MALFORMED
+                    ));
+                }
+            };
+            let value = parse_value(&format!("key `{}` of {}", key, what), value)?;
+            if map.insert(key.to_owned(), value).is_some() && duplicates == DuplicateKeys::Reject {
+                return Err(bad_usage!("Duplicate key `{}` for {}", key, what));
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opts::OptSpec;
+    use crate::test_helpers::parse_opts;
+    use getopts::{HasArg, Occur};
+
+    /// Parses `args` with a `-D/--define` map option.
+    fn parse(args: &[&str]) -> Matches {
+        let specs = [OptSpec::new("D", "define", "", "KEY=VALUE", HasArg::Yes, Occur::Multi)];
+        parse_opts(&specs, args)
+    }
+
+    /// Converts a slice of string pairs into a map.
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_split_entry() {
+        assert_eq!(Some(("a", "b")), split_entry("a=b"));
+        assert_eq!(Some(("a", "")), split_entry("a="));
+        assert_eq!(Some(("a", "b=c")), split_entry("a=b=c"));
+        assert_eq!(None, split_entry("a"));
+        assert_eq!(None, split_entry("=b"));
+    }
+
+    #[test]
+    fn test_matches_opt_map() {
+        let matches = parse(&[]);
+        assert_eq!(map(&[]), matches.opt_map("define", DuplicateKeys::Reject).unwrap());
+
+        let matches = parse(&["-Dz=1", "--define", "a=2=3", "-D", "m="]);
+        assert_eq!(
+            map(&[("a", "2=3"), ("m", ""), ("z", "1")]),
+            matches.opt_map("D", DuplicateKeys::Reject).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_matches_opt_map_duplicates() {
+        let matches = parse(&["-Da=1", "-Db=2", "-Da=3"]);
+        assert_eq!(
+            map(&[("a", "3"), ("b", "2")]),
+            matches.opt_map("define", DuplicateKeys::LastWins).unwrap()
+        );
+        assert_eq!(
+            bad_usage!("Duplicate key `a` for option --define"),
+            matches.opt_map("define", DuplicateKeys::Reject).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_matches_opt_map_malformed() {
+        let matches = parse(&["-Da=1", "-Dfoo"]);
+        assert_eq!(
+            bad_usage!("Invalid value `foo` for option --define: must be of the form KEY=VALUE"),
+            matches.opt_map("define", DuplicateKeys::LastWins).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_matches_opt_map_get() {
+        let matches = parse(&["-Da=1", "-Db=2"]);
+        let expected = BTreeMap::from([("a".to_owned(), 1), ("b".to_owned(), 2)]);
+        assert_eq!(expected, matches.opt_map_get::<i32>("define", DuplicateKeys::Reject).unwrap());
+
+        let matches = parse(&["-Da=1", "-Db=x"]);
+        assert_eq!(
+            bad_usage!(
+                "Invalid value `x` for key `b` of option --define: invalid digit found in string"
+            ),
+            matches.opt_map_get::<i32>("define", DuplicateKeys::Reject).unwrap_err()
+        );
+    }
+}
@@ -10,12 +10,191 @@
 
 //! Accessors for getopts.
 
-use crate::{Builder, Matches};
-use getopts::{HasArg, Occur};
+use crate::errors::{UsageError, parse_value};
+use crate::fallbacks::{Fallback, Source};
+use crate::os_args;
+use crate::validators::Validator;
+use crate::{Builder, Matches, OptionMatches};
+use getopts::{HasArg, Occur, Options};
+use std::fmt;
 use std::iter::IntoIterator;
 use std::str::FromStr;
 
+/// Specification of a single option as given to `getopts::Options::opt`, plus extra details
+/// handled by this crate.
+#[derive(Clone, Debug)]
+pub(crate) struct OptSpec {
+    pub(crate) short_name: String,
+    pub(crate) long_name: String,
+    desc: String,
+    hint: String,
+    pub(crate) hasarg: HasArg,
+    pub(crate) occur: Occur,
+
+    /// Name of the environment variable that provides the value of the option if not given.
+    pub(crate) env: Option<String>,
+
+    /// Functions to check the values of the option.
+    pub(crate) validators: Vec<Validator>,
+
+    /// Values that the option accepts, if restricted.
+    choices: Vec<String>,
+
+    /// Default value of a boolean option, which also accepts a `--no-` form to disable it.
+    pub(crate) bool_default: Option<bool>,
+
+    /// Delimiter between the elements of a list option.
+    pub(crate) delimiter: Option<char>,
+
+    /// Default value of the option, formatted as the user would type it, if known.
+    pub(crate) default: Option<String>,
+
+    /// Index of the command that defines the option among the commands in the command line,
+    /// where 0 is the outermost command.  Set when the command line is processed.
+    pub(crate) level: usize,
+}
+
+impl OptSpec {
+    /// Creates a new option specification.
+    pub(crate) fn new(
+        short_name: &str,
+        long_name: &str,
+        desc: &str,
+        hint: &str,
+        hasarg: HasArg,
+        occur: Occur,
+    ) -> Self {
+        Self {
+            short_name: short_name.to_owned(),
+            long_name: long_name.to_owned(),
+            desc: desc.to_owned(),
+            hint: hint.to_owned(),
+            hasarg,
+            occur,
+            env: None,
+            validators: vec![],
+            choices: vec![],
+            bool_default: None,
+            delimiter: None,
+            default: None,
+            level: 0,
+        }
+    }
+
+    /// Returns the name of the option to query matches, preferring the long form.
+    pub(crate) fn name(&self) -> &str {
+        if self.long_name.is_empty() { &self.short_name } else { &self.long_name }
+    }
+
+    /// Returns the name of the option as the user would type it, preferring the long form.
+    pub(crate) fn display_name(&self) -> String {
+        if self.long_name.is_empty() {
+            format!("-{}", self.short_name)
+        } else {
+            format!("--{}", self.long_name)
+        }
+    }
+
+    /// Returns the name of the `--no-` form of a boolean option, or `None` for other options.
+    pub(crate) fn negated_name(&self) -> Option<String> {
+        self.bool_default.map(|_| format!("no-{}", self.long_name))
+    }
+
+    /// Returns the description of the option for help messages.
+    fn help_desc(&self) -> String {
+        let mut desc = self.desc.clone();
+        if !self.choices.is_empty() {
+            desc.push_str(&format!(" (one of: {})", self.choices.join(", ")));
+        }
+        if let Some(default) = self.default.as_ref() {
+            desc.push_str(&format!(" (default: {})", default));
+        }
+        if let Some(env) = self.env.as_ref() {
+            desc.push_str(&format!(" [env: {}]", env));
+        }
+        desc
+    }
+
+    /// Returns true if this option is the option called `name`, which can be short or long.
+    pub(crate) fn is(&self, name: &str) -> bool {
+        (!self.short_name.is_empty() && self.short_name == name)
+            || (!self.long_name.is_empty() && self.long_name == name)
+    }
+
+    /// Returns true if this option and `other` share any of their names.
+    pub(crate) fn clashes(&self, other: &OptSpec) -> bool {
+        self.is(&other.short_name) || self.is(&other.long_name)
+    }
+}
+
+/// Creates a `getopts::Options` object with all the options in `specs`, either to parse the
+/// command line or, if `for_help` is true, to describe the options in help messages.
+///
+/// Required options are made optional because they can get their values from sources other than
+/// the command line.  These are checked once all sources are known.
+fn build<'a, I: IntoIterator<Item = &'a OptSpec>>(specs: I, for_help: bool) -> Options {
+    let mut opts = Options::new();
+    for spec in specs {
+        let occur = match spec.occur {
+            Occur::Req => Occur::Optional,
+            occur => occur,
+        };
+        match spec.negated_name() {
+            Some(_) if for_help => {
+                let long_name = format!("[no-]{}", spec.long_name);
+                opts.opt(&spec.short_name, &long_name, &spec.help_desc(), "", spec.hasarg, occur);
+            }
+            Some(negated_name) => {
+                opts.opt(&spec.short_name, &spec.long_name, "", "", spec.hasarg, occur);
+                opts.opt("", &negated_name, "", "", spec.hasarg, occur);
+            }
+            None => {
+                opts.opt(
+                    &spec.short_name,
+                    &spec.long_name,
+                    &spec.help_desc(),
+                    &spec.hint,
+                    spec.hasarg,
+                    occur,
+                );
+            }
+        }
+    }
+    opts
+}
+
+/// Creates a `getopts::Options` object to parse the command line with all the options in
+/// `specs`.
+pub(crate) fn to_getopts<'a, I: IntoIterator<Item = &'a OptSpec>>(specs: I) -> Options {
+    build(specs, false)
+}
+
+/// Creates a `getopts::Options` object to describe all the options in `specs` in help messages.
+///
+/// Boolean options are shown as a single `--[no-]name` entry.
+pub(crate) fn to_help<'a, I: IntoIterator<Item = &'a OptSpec>>(specs: I) -> Options {
+    build(specs, true)
+}
+
 impl Builder {
+    /// Makes the environment variable `var` provide the value of the option `name` when the
+    /// option is not given in the command line.
+    ///
+    /// For options that take an argument, the contents of the variable become the argument.  For
+    /// flags, the variable must contain a boolean value like `1`, `true` or `yes` to enable the
+    /// flag, or `0`, `false`, `no` or the empty string to leave it disabled.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the option name is not defined.
+    pub fn env(mut self, name: &str, var: &str) -> Self {
+        match self.opts.iter_mut().find(|spec| spec.is(name)) {
+            Some(spec) => spec.env = Some(var.to_owned()),
+            None => undefined(name),
+        }
+        self
+    }
+
     /// Trivial wrapper over `getopts::Options::opt`.
     #[inline(always)]
     pub fn opt(
@@ -27,147 +206,740 @@ impl Builder {
         hasarg: HasArg,
         occur: Occur,
     ) -> Self {
-        self.opts.opt(short_name, long_name, desc, hint, hasarg, occur);
+        self.opts.push(OptSpec::new(short_name, long_name, desc, hint, hasarg, occur));
+        self
+    }
+
+    /// Defines an option that takes an argument restricted to one of the given `choices`.
+    ///
+    /// Values outside of the set are rejected with a usage error, and the set is listed in the
+    /// help message.
+    pub fn optchoice(
+        mut self,
+        short_name: &str,
+        long_name: &str,
+        desc: &str,
+        hint: &str,
+        choices: &[&str],
+    ) -> Self {
+        assert!(!choices.is_empty(), "Choice options require at least one choice");
+        let mut spec =
+            OptSpec::new(short_name, long_name, desc, hint, HasArg::Yes, Occur::Optional);
+        spec.choices = choices.iter().map(|choice| choice.to_string()).collect();
+        let choices = spec.choices.clone();
+        spec.validators.push(Validator::new(move |value| {
+            if choices.iter().any(|choice| choice == value) {
+                Ok(())
+            } else {
+                Err(format!("must be one of: {}", choices.join(", ")))
+            }
+        }));
+        self.opts.push(spec);
+        self
+    }
+
+    /// Defines a boolean option that can be enabled with `--long_name` (or `-short_name`) and
+    /// disabled with `--no-long_name`, and that takes the `default` value if neither is given.
+    ///
+    /// The option is shown in the help message as `--[no-]long_name`.  Use `Matches::opt_bool`
+    /// to query its value.
+    pub fn optbool(mut self, short_name: &str, long_name: &str, default: bool, desc: &str) -> Self {
+        assert!(!long_name.is_empty(), "Boolean options require a long name");
+        let mut spec = OptSpec::new(short_name, long_name, desc, "", HasArg::No, Occur::Multi);
+        spec.bool_default = Some(default);
+        self.opts.push(spec);
         self
     }
 
     /// Trivial wrapper over `getopts::Options::optflag`.
     #[inline(always)]
-    pub fn optflag(mut self, short_name: &str, long_name: &str, desc: &str) -> Self {
-        self.opts.optflag(short_name, long_name, desc);
-        self
+    pub fn optflag(self, short_name: &str, long_name: &str, desc: &str) -> Self {
+        self.opt(short_name, long_name, desc, "", HasArg::No, Occur::Optional)
     }
 
     /// Trivial wrapper over `getopts::Options::optflagmulti`.
     #[inline(always)]
-    pub fn optflagmulti(mut self, short_name: &str, long_name: &str, desc: &str) -> Self {
-        self.opts.optflagmulti(short_name, long_name, desc);
-        self
+    pub fn optflagmulti(self, short_name: &str, long_name: &str, desc: &str) -> Self {
+        self.opt(short_name, long_name, desc, "", HasArg::No, Occur::Multi)
     }
 
     /// Trivial wrapper over `getopts::Options::optflagopt`.
     #[inline(always)]
-    pub fn optflagopt(mut self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
-        self.opts.optflagopt(short_name, long_name, desc, hint);
-        self
+    pub fn optflagopt(self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
+        self.opt(short_name, long_name, desc, hint, HasArg::Maybe, Occur::Optional)
     }
 
     /// Trivial wrapper over `getopts::Options::optmulti`.
     #[inline(always)]
-    pub fn optmulti(mut self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
-        self.opts.optmulti(short_name, long_name, desc, hint);
-        self
+    pub fn optmulti(self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
+        self.opt(short_name, long_name, desc, hint, HasArg::Yes, Occur::Multi)
     }
 
     /// Trivial wrapper over `getopts::Options::optopt`.
     #[inline(always)]
-    pub fn optopt(mut self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
-        self.opts.optopt(short_name, long_name, desc, hint);
-        self
+    pub fn optopt(self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
+        self.opt(short_name, long_name, desc, hint, HasArg::Yes, Occur::Optional)
     }
 
     /// Trivial wrapper over `getopts::Options::reqopt`.
     #[inline(always)]
-    pub fn reqopt(mut self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
-        self.opts.reqopt(short_name, long_name, desc, hint);
-        self
+    pub fn reqopt(self, short_name: &str, long_name: &str, desc: &str, hint: &str) -> Self {
+        self.opt(short_name, long_name, desc, hint, HasArg::Yes, Occur::Req)
     }
 }
 
+/// Fails in the same way as `getopts` does when querying an option that has not been defined.
+pub(crate) fn undefined(name: &str) -> ! {
+    panic!("No option '{}' defined", name)
+}
+
 impl Matches {
-    /// Trivial wrapper over `getopts::Matches::opt_defined`.
-    #[inline(always)]
+    /// Returns the index of the outermost command whose matches may contain the option `name`,
+    /// or the number of commands if the innermost command does not know about `name`.
+    ///
+    /// Commands above the one that defines the option know nothing about it, and may define an
+    /// unrelated option with the same name that must not leak into the subcommand.
+    fn first_level(&self, name: &str) -> usize {
+        self.specs
+            .iter()
+            .find(|spec| spec.is(name) || spec.negated_name().as_deref() == Some(name))
+            .map(|spec| spec.level)
+            .unwrap_or(self.opts.len())
+    }
+
+    /// Returns the matches of the innermost command where `name` was given.  If `name` was not
+    /// given anywhere, returns the matches of the innermost command that defines it.
+    ///
+    /// Options of a command are also accepted by all of its subcommands, so a single option may
+    /// have been given at different levels of the command line.  The innermost value wins.
+    pub(crate) fn opt_level(&self, name: &str) -> &OptionMatches {
+        let mut defined = None;
+        for (_offset, opts) in self.opts[self.first_level(name)..].iter().rev() {
+            if opts.opt_defined(name) {
+                if opts.opt_present(name) {
+                    return opts;
+                }
+                defined = defined.or(Some(opts));
+            }
+        }
+        defined.unwrap_or_else(|| undefined(name))
+    }
+
+    /// Returns the matches of all commands that define `name`, from the outermost, along with
+    /// the offset to apply to their positions.
+    fn opt_levels<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a (usize, OptionMatches)> {
+        if !self.opt_defined(name) {
+            undefined(name);
+        }
+        let first_level = self.first_level(name);
+        self.opts[first_level..].iter().filter(move |(_offset, opts)| opts.opt_defined(name))
+    }
+
+    /// Returns the specification of the option `name`.
+    pub(crate) fn spec(&self, name: &str) -> &OptSpec {
+        self.specs.iter().find(|spec| spec.is(name)).unwrap_or_else(|| undefined(name))
+    }
+
+    /// Returns true if `name` was given in the command line, ignoring any other sources.
+    pub(crate) fn opt_given(&self, name: &str) -> bool {
+        self.opt_level(name).opt_present(name)
+    }
+
+    /// Returns the values of `name` that did not come from the command line, if any.
+    ///
+    /// These only exist when the option was not given in the command line.
+    pub(crate) fn fallback(&self, name: &str) -> Option<&Fallback> {
+        self.fallbacks.iter().find(|fallback| fallback.spec.is(name))
+    }
+
+    /// Same as `fallback` but ignores the values that turn off a boolean option.
+    fn enabled_fallback(&self, name: &str) -> Option<&Fallback> {
+        self.fallback(name).filter(|fallback| !fallback.is_disabled())
+    }
+
+    /// Returns the source of the value of `name` returned by `opt_str`, or `None` if the value
+    /// came from the command line.
+    fn fallback_source(&self, name: &str) -> Option<&Source> {
+        self.fallback(name).map(|fallback| &fallback.sources[0])
+    }
+
+    /// Same as `opt_str` but pairs the value with a description of the option for error
+    /// messages, which also says where the value came from if it was not the command line.
+    ///
+    /// Returns a `UsageError` if the value is not valid UTF-8.
+    pub(crate) fn opt_str_described(
+        &self,
+        name: &str,
+    ) -> Result<Option<(String, String)>, UsageError> {
+        let mut what = format!("option {}", self.spec(name).display_name());
+        match self.fallback_value(name) {
+            Some(value) => {
+                if let Some(source) = self.fallback_source(name) {
+                    what.push_str(&format!(" in {}", source));
+                }
+                Ok(value.map(|value| (what, value.to_owned())))
+            }
+            None => match self.opt_level(name).opt_str(name) {
+                Some(value) => match self.decode(&value) {
+                    Ok(value) => Ok(Some((what, value))),
+                    Err(lossy) => Err(os_args::invalid_utf8(&what, &lossy)),
+                },
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Same as `opt_strs` but pairs every value with a description of the option for error
+    /// messages, which also says where the value came from if it was not the command line.
+    ///
+    /// Returns a `UsageError` if any value is not valid UTF-8.
+    pub(crate) fn opt_strs_described(
+        &self,
+        name: &str,
+    ) -> Result<Vec<(String, String)>, UsageError> {
+        let what = format!("option {}", self.spec(name).display_name());
+        match self.fallback(name) {
+            Some(fallback) => Ok(fallback
+                .values
+                .iter()
+                .zip(&fallback.sources)
+                .filter_map(|(value, source)| {
+                    value.as_ref().map(|value| (format!("{} in {}", what, source), value.clone()))
+                })
+                .collect()),
+            None => self
+                .opt_levels(name)
+                .flat_map(|(_offset, opts)| opts.opt_strs(name))
+                .map(|value| match self.decode(&value) {
+                    Ok(value) => Ok((what.clone(), value)),
+                    Err(lossy) => Err(os_args::invalid_utf8(&what, &lossy)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the first value of `name` that did not come from the command line, if any.
+    ///
+    /// The outer option indicates whether the option has fallback values at all and the inner
+    /// option carries the value of its first occurrence.
+    pub(crate) fn fallback_value(&self, name: &str) -> Option<Option<&str>> {
+        self.fallback(name).map(|fallback| fallback.values[0].as_deref())
+    }
+
+    /// Returns true if `name` is an option of the command, including the options that the
+    /// command inherits from its parents.
     pub fn opt_defined(&self, name: &str) -> bool {
-        self.opts.opt_defined(name)
+        self.opts[self.first_level(name)..].iter().any(|(_offset, opts)| opts.opt_defined(name))
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_present`.
-    #[inline(always)]
+    /// Returns true if the option `name` was given in the command line of this command or of any
+    /// of its parents, or if it got a value from the environment or a configuration file.  A
+    /// boolean option that these sources turn off is not present.
     pub fn opt_present(&self, name: &str) -> bool {
-        self.opts.opt_present(name)
+        self.opt_level(name).opt_present(name) || self.enabled_fallback(name).is_some()
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_count`.
-    #[inline(always)]
+    /// Returns the number of times the option `name` was given across the command lines of this
+    /// command and of its parents.  If the option was not given in the command line, returns the
+    /// number of values it got from the environment or configuration files, except if they turn
+    /// off a boolean option.
     pub fn opt_count(&self, name: &str) -> usize {
-        self.opts.opt_count(name)
+        match self.enabled_fallback(name) {
+            Some(fallback) => fallback.values.len(),
+            None => self.opt_levels(name).map(|(_offset, opts)| opts.opt_count(name)).sum(),
+        }
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_positions`.
-    #[inline(always)]
+    /// Returns the positions where the option `name` was given across the command lines of this
+    /// command and of its parents.
+    ///
+    /// Positions are offsets into the whole command line, excluding the program name, not into
+    /// the arguments of the subcommand.  Values that did not come from the command line have no
+    /// position.
     pub fn opt_positions(&self, name: &str) -> Vec<usize> {
-        self.opts.opt_positions(name)
+        self.opt_levels(name)
+            .flat_map(|(offset, opts)| {
+                opts.opt_positions(name).into_iter().map(move |p| p + offset)
+            })
+            .collect()
     }
 
-    /// Trivial wrapper over `getopts::Matches::opts_present`.
-    #[inline(always)]
+    /// Returns true if any of the options in `names` is present as defined by `opt_present`.
+    /// Names that are not options of this command are ignored.
     pub fn opts_present(&self, names: &[String]) -> bool {
-        self.opts.opts_present(names)
+        names.iter().any(|name| self.opt_defined(name) && self.opt_present(name))
     }
 
-    /// Trivial wrapper over `getopts::Matches::opts_present_any`.
-    #[inline(always)]
+    /// Same as `opts_present` but takes any collection of names.
     pub fn opts_present_any<C: IntoIterator>(&self, names: C) -> bool
     where
         C::Item: AsRef<str>,
     {
-        self.opts.opts_present_any(names)
+        names
+            .into_iter()
+            .any(|name| self.opt_defined(name.as_ref()) && self.opt_present(name.as_ref()))
     }
 
-    /// Trivial wrapper over `getopts::Matches::opts_str`.
-    #[inline(always)]
+    /// Returns the value of the first option in `names` that has a value as returned by
+    /// `opt_str`.
     pub fn opts_str(&self, names: &[String]) -> Option<String> {
-        self.opts.opts_str(names)
+        names.iter().filter_map(|name| self.opt_str(name)).next()
     }
 
-    /// Trivial wrapper over `getopts::Matches::opts_str_first`.
-    #[inline(always)]
+    /// Same as `opts_str` but takes any collection of names.
     pub fn opts_str_first<C: IntoIterator>(&self, names: C) -> Option<String>
     where
         C::Item: AsRef<str>,
     {
-        self.opts.opts_str_first(names)
+        names.into_iter().filter_map(|name| self.opt_str(name.as_ref())).next()
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_strs`.
-    #[inline(always)]
+    /// Returns all the values of the option `name` across the command lines of this command and
+    /// of its parents, from the outermost.  If the option was not given in the command line,
+    /// returns the values it got from the environment or configuration files.
+    ///
+    /// Invalid UTF-8 sequences in the values are replaced by the Unicode replacement character.
     pub fn opt_strs(&self, name: &str) -> Vec<String> {
-        self.opts.opt_strs(name)
+        match self.fallback(name) {
+            Some(fallback) => fallback.values.iter().flatten().cloned().collect(),
+            None => self
+                .opt_levels(name)
+                .flat_map(|(_offset, opts)| opts.opt_strs(name))
+                .map(|value| self.decode_lossy(&value))
+                .collect(),
+        }
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_strs_pos`.
-    #[inline(always)]
+    /// Returns the values of the option `name` across the command lines of this command and of
+    /// its parents along with their positions, which are offsets into the whole command line as
+    /// in `opt_positions`.  Values that did not come from the command line have no position and
+    /// are not returned.
     pub fn opt_strs_pos(&self, name: &str) -> Vec<(usize, String)> {
-        self.opts.opt_strs_pos(name)
+        self.opt_levels(name)
+            .flat_map(|(offset, opts)| {
+                opts.opt_strs_pos(name)
+                    .into_iter()
+                    .map(move |(p, s)| (p + offset, self.decode_lossy(&s)))
+            })
+            .collect()
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_str`.
-    #[inline(always)]
+    /// Returns the value of the option `name` given in the innermost command, or the value that
+    /// it got from the environment or a configuration file if it was not given in the command
+    /// line.
+    ///
+    /// Invalid UTF-8 sequences in the value are replaced by the Unicode replacement character.
     pub fn opt_str(&self, name: &str) -> Option<String> {
-        self.opts.opt_str(name)
+        match self.fallback_value(name) {
+            Some(value) => value.map(str::to_owned),
+            None => self.opt_level(name).opt_str(name).map(|value| self.decode_lossy(&value)),
+        }
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_default`.
-    #[inline(always)]
+    /// Same as `opt_str` but returns `def` if the option is present without a value.
     pub fn opt_default(&self, name: &str, def: &str) -> Option<String> {
-        self.opts.opt_default(name, def)
+        match self.fallback_value(name) {
+            Some(value) => Some(value.unwrap_or(def).to_owned()),
+            None => {
+                let opts = self.opt_level(name);
+                if !opts.opt_present(name) {
+                    return None;
+                }
+                Some(opts.opt_str(name).map_or_else(|| def.to_owned(), |v| self.decode_lossy(&v)))
+            }
+        }
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_get`.
-    #[inline(always)]
+    /// Same as `opt_str` but parses the value into a `T`.
     pub fn opt_get<T>(&self, name: &str) -> Result<Option<T>, T::Err>
     where
         T: FromStr,
     {
-        self.opts.opt_get(name)
+        match self.opt_str(name) {
+            Some(value) => Ok(Some(value.parse()?)),
+            None => Ok(None),
+        }
     }
 
-    /// Trivial wrapper over `getopts::Matches::opt_get_default`.
-    #[inline(always)]
+    /// Same as `opt_get` but returns a `UsageError` that names the option and its value if the
+    /// value is invalid, as well as the environment variable or configuration file that provided
+    /// the value if it did not come from the command line.
+    pub fn opt_get_usage<T>(&self, name: &str) -> Result<Option<T>, UsageError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match self.opt_str_described(name)? {
+            Some((what, value)) => parse_value(&what, &value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value of the boolean option `name`.
+    ///
+    /// The last of `--name` and `--no-name` given in the command line wins.  If neither is given,
+    /// the value comes from the environment or the configuration files, if any, or from the
+    /// default value of the option.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `name` is not a boolean option.
+    pub fn opt_bool(&self, name: &str) -> bool {
+        let spec = self.spec(name);
+        let (default, negated_name) = match (spec.bool_default, spec.negated_name()) {
+            (Some(default), Some(negated_name)) => (default, negated_name),
+            _ => panic!("Option '{}' is not a boolean option", name),
+        };
+        let enabled = self.opt_positions(spec.name()).into_iter().max();
+        let disabled = self.opt_positions(&negated_name).into_iter().max();
+        match (enabled, disabled) {
+            (None, None) => match self.fallback_value(name) {
+                Some(value) => value == Some("true"),
+                None => default,
+            },
+            (enabled, disabled) => enabled > disabled,
+        }
+    }
+
+    /// Returns the value of the choice option `name` converted to a `T`, which can be a `String`
+    /// or a user-defined type, such as an enum, whose `FromStr` implementation accepts all of the
+    /// choices.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `T` cannot be created from the selected choice.
+    pub fn opt_choice<T>(&self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Debug,
+    {
+        self.opt_str(name).map(|value| match value.parse() {
+            Ok(value) => value,
+            Err(e) => panic!("Choice `{}` of option '{}' is not convertible: {:?}", value, name, e),
+        })
+    }
+
+    /// Same as `opt_get` but returns `def` if the option does not have a value.
     pub fn opt_get_default<T>(&self, name: &str, def: T) -> Result<T, T::Err>
     where
         T: FromStr,
     {
-        self.opts.opt_get_default(name, def)
+        match self.opt_str(name) {
+            Some(value) => value.parse(),
+            None => Ok(def),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Arguments;
+    use crate::errors::bad_usage;
+    use crate::test_helpers::parse_matches;
+    use std::path::PathBuf;
+
+    /// Parses `levels` of arguments with `opts` as if each level was a nested subcommand.
+    fn parse_levels(opts: &[OptSpec], levels: &[&[&str]]) -> Matches {
+        parse_matches(opts, &Arguments::default(), levels)
+    }
+
+    #[test]
+    fn test_optspec_is() {
+        let spec = OptSpec::new("a", "all", "", "", HasArg::No, Occur::Optional);
+        assert!(spec.is("a"));
+        assert!(spec.is("all"));
+        assert!(!spec.is("b"));
+        assert!(!spec.is(""));
+    }
+
+    #[test]
+    fn test_optspec_clashes() {
+        let spec1 = OptSpec::new("a", "all", "", "", HasArg::No, Occur::Optional);
+        let spec2 = OptSpec::new("", "all", "", "", HasArg::No, Occur::Optional);
+        let spec3 = OptSpec::new("a", "", "", "", HasArg::No, Occur::Optional);
+        let spec4 = OptSpec::new("", "any", "", "", HasArg::No, Occur::Optional);
+        assert!(spec1.clashes(&spec2));
+        assert!(spec1.clashes(&spec3));
+        assert!(!spec2.clashes(&spec3));
+        assert!(!spec1.clashes(&spec4));
+    }
+
+    #[test]
+    fn test_matches_fallbacks() {
+        let opts = [
+            OptSpec::new("a", "", "", "", HasArg::No, Occur::Optional),
+            OptSpec::new("b", "", "", "", HasArg::Yes, Occur::Multi),
+            OptSpec::new("c", "", "", "", HasArg::Yes, Occur::Optional),
+        ];
+        let mut matches = parse_levels(&opts, &[&["-c", "cmdline"]]);
+        matches.fallbacks = vec![
+            Fallback {
+                spec: opts[0].clone(),
+                values: vec![None],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+            Fallback {
+                spec: opts[1].clone(),
+                values: vec![Some("1".to_owned())],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+        ];
+
+        assert!(matches.opt_present("a"));
+        assert_eq!(1, matches.opt_count("a"));
+        assert!(matches.opt_positions("a").is_empty());
+        assert_eq!(vec!["1".to_owned()], matches.opt_strs("b"));
+        assert_eq!(Some(1), matches.opt_get::<i32>("b").unwrap());
+        assert_eq!(Some("cmdline".to_owned()), matches.opt_str("c"));
+        assert!(matches.opts_present(&["x".to_owned(), "b".to_owned()]));
+    }
+
+    #[test]
+    fn test_matches_fallback_sources() {
+        let opts = [
+            OptSpec::new("j", "jobs", "", "", HasArg::Yes, Occur::Optional),
+            OptSpec::new("", "include", "", "", HasArg::Yes, Occur::Multi),
+        ];
+        let mut matches = parse_levels(&opts, &[&[]]);
+        matches.fallbacks = vec![
+            Fallback {
+                spec: opts[0].clone(),
+                values: vec![Some("abc".to_owned())],
+                sources: vec![Source::Env("MYTOOL_JOBS".to_owned())],
+            },
+            Fallback {
+                spec: opts[1].clone(),
+                values: vec![Some("a".to_owned()), Some("b".to_owned())],
+                sources: vec![
+                    Source::File(PathBuf::from("tool.conf"), 2),
+                    Source::File(PathBuf::from("tool.conf"), 5),
+                ],
+            },
+        ];
+
+        assert_eq!(
+            bad_usage!(
+                "Invalid value `abc` for option --jobs in environment variable MYTOOL_JOBS: \
+                 invalid digit found in string"
+            ),
+            matches.opt_get_usage::<u32>("jobs").unwrap_err()
+        );
+        assert_eq!(
+            Some((
+                "option --jobs in environment variable MYTOOL_JOBS".to_owned(),
+                "abc".to_owned()
+            )),
+            matches.opt_str_described("j").unwrap()
+        );
+        assert_eq!(
+            vec![
+                ("option --include in tool.conf:2".to_owned(), "a".to_owned()),
+                ("option --include in tool.conf:5".to_owned(), "b".to_owned()),
+            ],
+            matches.opt_strs_described("include").unwrap()
+        );
+
+        let matches = parse_levels(&opts, &[&["--include=c"]]);
+        assert_eq!(
+            vec![("option --include".to_owned(), "c".to_owned())],
+            matches.opt_strs_described("include").unwrap()
+        );
+    }
+
+    /// Creates the specification of a boolean option called `name` with a `default` value.
+    fn bool_spec(short_name: &str, long_name: &str, default: bool) -> OptSpec {
+        let mut spec = OptSpec::new(short_name, long_name, "", "", HasArg::No, Occur::Multi);
+        spec.bool_default = Some(default);
+        spec
+    }
+
+    #[test]
+    fn test_to_help_bool() {
+        let opts = [bool_spec("c", "color", true), bool_spec("", "pager", false)];
+        let usage = to_help(&opts).usage("Usage: x");
+        assert!(usage.contains("    -c, --[no-]color "), "Got: {}", usage);
+        assert!(usage.contains("        --[no-]pager "), "Got: {}", usage);
+        assert!(!usage.contains("--no-color"), "Got: {}", usage);
+    }
+
+    #[test]
+    fn test_matches_opt_bool() {
+        let opts = [bool_spec("c", "color", true), bool_spec("", "pager", false)];
+
+        let matches = parse_levels(&opts, &[&[]]);
+        assert!(matches.opt_bool("color"));
+        assert!(!matches.opt_bool("pager"));
+
+        let matches = parse_levels(&opts, &[&["--no-color", "--pager"]]);
+        assert!(!matches.opt_bool("c"));
+        assert!(matches.opt_bool("pager"));
+
+        let matches = parse_levels(&opts, &[&["--no-color", "-c", "--pager"], &["--no-pager"]]);
+        assert!(matches.opt_bool("color"));
+        assert!(!matches.opt_bool("pager"));
+    }
+
+    #[test]
+    fn test_matches_opt_bool_fallbacks() {
+        let opts = [bool_spec("", "color", true), bool_spec("", "pager", false)];
+        let mut matches = parse_levels(&opts, &[&["--no-pager"]]);
+        matches.fallbacks = vec![
+            Fallback {
+                spec: opts[0].clone(),
+                values: vec![Some("false".to_owned())],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+            Fallback {
+                spec: opts[1].clone(),
+                values: vec![Some("true".to_owned())],
+                sources: vec![Source::Env("VAR".to_owned())],
+            },
+        ];
+        assert!(!matches.opt_bool("color"));
+        assert!(!matches.opt_bool("pager"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Option 'a' is not a boolean option")]
+    fn test_matches_opt_bool_not_bool() {
+        let opts = [OptSpec::new("a", "", "", "", HasArg::No, Occur::Optional)];
+        parse_levels(&opts, &[&[]]).opt_bool("a");
+    }
+
+    #[test]
+    fn test_matches_opt_get_usage() {
+        let opts = [
+            OptSpec::new("j", "", "", "", HasArg::Yes, Occur::Optional),
+            OptSpec::new("", "count", "", "", HasArg::Yes, Occur::Optional),
+            OptSpec::new("s", "size", "", "", HasArg::Yes, Occur::Optional),
+        ];
+        let matches = parse_levels(&opts, &[&["-j", "4"]]);
+        assert_eq!(Some(4), matches.opt_get_usage::<u32>("j").unwrap());
+        assert_eq!(None, matches.opt_get_usage::<u32>("count").unwrap());
+
+        let matches = parse_levels(&opts, &[&["-j", "-1", "--count=x", "-s", "y"]]);
+        assert_eq!(
+            bad_usage!("Invalid value `-1` for option -j: invalid digit found in string"),
+            matches.opt_get_usage::<u32>("j").unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Invalid value `x` for option --count: invalid digit found in string"),
+            matches.opt_get_usage::<u32>("count").unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Invalid value `y` for option --size: invalid digit found in string"),
+            matches.opt_get_usage::<u32>("s").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_optspec_help_desc() {
+        let mut spec =
+            OptSpec::new("", "color", "when to use colors", "", HasArg::Yes, Occur::Optional);
+        assert_eq!("when to use colors", spec.help_desc());
+        spec.choices = vec!["auto".to_owned(), "never".to_owned()];
+        assert_eq!("when to use colors (one of: auto, never)", spec.help_desc());
+        spec.env = Some("COLOR".to_owned());
+        assert_eq!("when to use colors (one of: auto, never) [env: COLOR]", spec.help_desc());
+        spec.default = Some("auto".to_owned());
+        assert_eq!(
+            "when to use colors (one of: auto, never) (default: auto) [env: COLOR]",
+            spec.help_desc()
+        );
+    }
+
+    #[test]
+    fn test_matches_opt_choice() {
+        #[derive(Debug, PartialEq)]
+        enum Color {
+            Auto,
+            Never,
+        }
+
+        impl FromStr for Color {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    "auto" => Ok(Color::Auto),
+                    "never" => Ok(Color::Never),
+                    _ => Err(format!("unknown color {}", s)),
+                }
+            }
+        }
+
+        let opts = [OptSpec::new("", "color", "", "", HasArg::Yes, Occur::Optional)];
+        let matches = parse_levels(&opts, &[&[]]);
+        assert_eq!(None, matches.opt_choice::<Color>("color"));
+        let matches = parse_levels(&opts, &[&["--color=never"]]);
+        assert_eq!(Some(Color::Never), matches.opt_choice::<Color>("color"));
+        assert_eq!(Some("never".to_owned()), matches.opt_choice::<String>("color"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Choice `other` of option 'color' is not convertible")]
+    fn test_matches_opt_choice_not_convertible() {
+        let opts = [OptSpec::new("", "color", "", "", HasArg::Yes, Occur::Optional)];
+        let matches = parse_levels(&opts, &[&["--color=other"]]);
+        matches.opt_choice::<u32>("color");
+    }
+
+    #[test]
+    fn test_matches_levels_innermost_wins() {
+        let opts = [OptSpec::new("o", "", "", "VALUE", HasArg::Yes, Occur::Optional)];
+        let matches = parse_levels(&opts, &[&["-o", "outer"], &[], &["-o", "inner"]]);
+        assert_eq!(Some("inner"), matches.opt_str("o").as_deref());
+        assert_eq!(vec!["outer", "inner"], matches.opt_strs("o"));
+        assert_eq!(
+            vec![(0, "outer".to_owned()), (2, "inner".to_owned())],
+            matches.opt_strs_pos("o")
+        );
+
+        let matches = parse_levels(&opts, &[&["-o", "outer"], &[]]);
+        assert_eq!(Some("outer"), matches.opt_str("o").as_deref());
+    }
+
+    #[test]
+    fn test_matches_levels_counts() {
+        let opts = [OptSpec::new("v", "", "", "", HasArg::No, Occur::Multi)];
+        let matches = parse_levels(&opts, &[&["-v"], &[], &["-vv"]]);
+        assert!(matches.opt_present("v"));
+        assert_eq!(3, matches.opt_count("v"));
+        assert_eq!(vec![0, 1, 1], matches.opt_positions("v"));
+
+        let matches = parse_levels(&opts, &[&[], &[]]);
+        assert!(!matches.opt_present("v"));
+        assert_eq!(0, matches.opt_count("v"));
+    }
+
+    #[test]
+    fn test_matches_levels_shadowed() {
+        let verbose = OptSpec::new("v", "verbose", "", "", HasArg::No, Occur::Optional);
+        let mut value = OptSpec::new("v", "value", "", "", HasArg::No, Occur::Optional);
+        value.level = 1;
+        let parent = to_getopts([&verbose]).parse(["-v"]).unwrap();
+        let child = to_getopts([&value]).parse(Vec::<String>::new()).unwrap();
+        let matches = Matches {
+            program_name: "irrelevant".to_owned(),
+            opts: vec![(0, parent), (1, child)],
+            args: Arguments::default().parse(Vec::<String>::new()).unwrap(),
+            fallbacks: vec![],
+            specs: vec![value],
+            escaped: true,
+        };
+        assert!(!matches.opt_present("v"));
+        assert!(!matches.opt_present("value"));
+        assert_eq!(0, matches.opt_count("v"));
+        assert!(matches.opt_positions("v").is_empty());
+        assert!(!matches.opt_defined("verbose"));
+        assert!(!matches.opts_present(&["v".to_owned(), "verbose".to_owned()]));
+    }
+
+    #[test]
+    #[should_panic(expected = "No option 'x' defined")]
+    fn test_matches_levels_undefined() {
+        let matches = parse_levels(&[], &[&[], &[]]);
+        matches.opt_count("x");
     }
 }
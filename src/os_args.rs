@@ -0,0 +1,316 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Support for arguments that are not valid UTF-8.
+//!
+//! getopts only handles UTF-8 strings, so arguments that are not valid UTF-8 are escaped into
+//! strings before parsing and unescaped when queried.  The `_os` accessors return the exact
+//! arguments while the string accessors return their text, replacing invalid UTF-8 sequences,
+//! and the fallible string accessors and validators report those values as usage errors.  On Unix, every
+//! byte that is not part of a valid UTF-8 sequence becomes a character in a range of the last
+//! private use plane.  Characters that already fall in that range are escaped byte by byte too so
+//! that they cannot be confused with escaped bytes, which makes the conversion lossless.
+//! Elsewhere, invalid sequences are replaced with the Unicode replacement character when
+//! unescaped.
+
+use crate::Matches;
+use crate::errors::{UsageError, bad_usage};
+use std::ffi::OsString;
+
+/// First character of the range used to escape bytes that are not valid UTF-8.  The byte value
+/// is added to this character.
+const ESCAPE_BASE: u32 = 0x10FF00;
+
+/// Returns the byte escaped by `ch`, if `ch` is an escape character.
+fn escaped_byte(ch: char) -> Option<u8> {
+    u32::from(ch).checked_sub(ESCAPE_BASE).and_then(|byte| u8::try_from(byte).ok())
+}
+
+/// Returns the character that escapes `byte`.
+fn escape_byte(byte: u8) -> char {
+    char::from_u32(ESCAPE_BASE + u32::from(byte)).expect("Escape range must be valid")
+}
+
+/// Escapes the characters of the valid UTF-8 string `s` that fall in the escape range.
+fn escape_str(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match escaped_byte(ch) {
+            Some(_) => {
+                escaped.extend(ch.encode_utf8(&mut [0; 4]).bytes().map(escape_byte));
+            }
+            None => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Converts `arg` into a string, escaping any invalid UTF-8 sequences.
+#[cfg(unix)]
+pub(crate) fn escape(arg: OsString) -> String {
+    use std::os::unix::ffi::OsStringExt;
+
+    match arg.into_string() {
+        Ok(arg) => escape_str(&arg),
+        Err(arg) => {
+            let mut escaped = String::new();
+            for chunk in arg.into_vec().utf8_chunks() {
+                escaped.push_str(&escape_str(chunk.valid()));
+                escaped.extend(chunk.invalid().iter().copied().map(escape_byte));
+            }
+            escaped
+        }
+    }
+}
+
+/// Converts `arg` into a string, escaping any invalid UTF-8 sequences.
+#[cfg(not(unix))]
+pub(crate) fn escape(arg: OsString) -> String {
+    match arg.into_string() {
+        Ok(arg) => escape_str(&arg),
+        Err(arg) => {
+            escape_str(&arg.to_string_lossy()).replace('\u{FFFD}', &escape_byte(0xFF).to_string())
+        }
+    }
+}
+
+/// Converts the `escaped` string back into the bytes of the argument it came from.
+fn unescape_bytes(escaped: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    for ch in escaped.chars() {
+        match escaped_byte(ch) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Converts the `escaped` string back into the argument it came from.
+#[cfg(unix)]
+pub(crate) fn unescape(escaped: &str) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+
+    if !escaped.chars().any(|ch| escaped_byte(ch).is_some()) {
+        return OsString::from(escaped);
+    }
+    OsString::from_vec(unescape_bytes(escaped))
+}
+
+/// Converts the `escaped` string back into the argument it came from.
+#[cfg(not(unix))]
+pub(crate) fn unescape(escaped: &str) -> OsString {
+    OsString::from(String::from_utf8_lossy(&unescape_bytes(escaped)).into_owned())
+}
+
+/// Converts the `escaped` string back into the text of the argument it came from.  If the
+/// argument is not valid UTF-8, returns its text with the invalid sequences replaced by the
+/// Unicode replacement character as the error.
+pub(crate) fn decode(escaped: &str) -> Result<String, String> {
+    if !escaped.chars().any(|ch| escaped_byte(ch).is_some()) {
+        return Ok(escaped.to_owned());
+    }
+    String::from_utf8(unescape_bytes(escaped))
+        .map_err(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Returns the error for the `lossy` text of a value for `what` that is not valid UTF-8.
+pub(crate) fn invalid_utf8(what: &str, lossy: &str) -> UsageError {
+    bad_usage!("Invalid UTF-8 in value `{}` for {}", lossy, what)
+}
+
+/// Converts the escaped `args` back into the arguments they came from, which must be valid UTF-8.
+pub(crate) fn to_utf8(args: Vec<String>) -> Result<Vec<String>, UsageError> {
+    args.into_iter()
+        .map(|arg| {
+            decode(&arg).map_err(|lossy| bad_usage!("Invalid UTF-8 in argument `{}`", lossy))
+        })
+        .collect()
+}
+
+/// Converts the `args` of a command back into the arguments they came from, unescaping them if
+/// they were processed with `escape`.
+pub(crate) fn to_os(args: Vec<String>, escaped: bool) -> Vec<OsString> {
+    if escaped {
+        args.iter().map(|arg| unescape(arg)).collect()
+    } else {
+        args.into_iter().map(OsString::from).collect()
+    }
+}
+
+impl Matches {
+    /// Converts the command line `value` back into the text of the argument it came from, or
+    /// returns its lossy text as the error if it is not valid UTF-8.
+    pub(crate) fn decode(&self, value: &str) -> Result<String, String> {
+        if self.escaped { decode(value) } else { Ok(value.to_owned()) }
+    }
+
+    /// Same as `decode` but returns the lossy text if `value` is not valid UTF-8.
+    pub(crate) fn decode_lossy(&self, value: &str) -> String {
+        self.decode(value).unwrap_or_else(|lossy| lossy)
+    }
+
+    /// Same as `arg_pos` but returns the exact value given in the command line even if it is not
+    /// valid UTF-8.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the argument name has not been correctly processed.
+    pub fn arg_pos_os(&self, name: &str) -> OsString {
+        self.args.positional_os(name).to_owned()
+    }
+
+    /// Same as `arg_trail` but returns the exact values given in the command line even if they
+    /// are not valid UTF-8.
+    pub fn arg_trail_os(&self) -> Vec<OsString> {
+        self.args.trailing_os().to_vec()
+    }
+
+    /// Same as `opt_str` but returns the exact value given in the command line even if it is not
+    /// valid UTF-8.
+    pub fn opt_str_os(&self, name: &str) -> Option<OsString> {
+        match self.fallback_value(name) {
+            Some(value) => value.map(OsString::from),
+            None => self
+                .opt_level(name)
+                .opt_str(name)
+                .map(|value| if self.escaped { unescape(&value) } else { OsString::from(value) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opts::OptSpec;
+    use crate::test_helpers::parse_opts;
+    use crate::{Builder, Outcome};
+    use getopts::{HasArg, Occur};
+    use std::iter;
+
+    #[test]
+    fn test_escape_valid() {
+        assert_eq!("foo bar", escape(OsString::from("foo bar")));
+        assert_eq!(OsString::from("foo bar"), unescape("foo bar"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_escape_round_trip() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let arg = OsString::from_vec(b"a\xffb\xc3\xa9c\xc3".to_vec());
+        let escaped = escape(arg.clone());
+        assert_eq!("a\u{10FFFF}béc\u{10FFC3}", escaped);
+        assert_eq!(arg, unescape(&escaped));
+    }
+
+    #[test]
+    fn test_escape_round_trip_escape_range() {
+        let arg = "a\u{10FFFF}b\u{10FF00}";
+        let escaped = escape(OsString::from(arg));
+        assert_eq!(
+            "a\u{10FFF4}\u{10FF8F}\u{10FFBF}\u{10FFBF}b\u{10FFF4}\u{10FF8F}\u{10FFBC}\u{10FF80}",
+            escaped
+        );
+        assert_eq!(OsString::from(arg), unescape(&escaped));
+        assert_eq!(vec![arg.to_owned()], to_utf8(vec![escaped]).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_to_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let args = vec!["ok".to_owned(), escape(OsString::from_vec(b"bad\xff".to_vec()))];
+        assert_eq!(vec!["ok".to_owned()], to_utf8(args[0..1].to_vec()).unwrap());
+        assert_eq!(
+            bad_usage!("Invalid UTF-8 in argument `bad\u{FFFD}`"),
+            to_utf8(args).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_to_os() {
+        let arg = "a\u{10FFFF}".to_owned();
+        assert_eq!(vec![OsString::from(&arg)], to_os(vec![escape(OsString::from(&arg))], true));
+        assert_eq!(vec![OsString::from(&arg)], to_os(vec![arg.clone()], false));
+    }
+
+    #[test]
+    fn test_matches_opt_str_os() {
+        let specs = [OptSpec::new("o", "opt", "", "", HasArg::Yes, Occur::Optional)];
+        let arg = "a\u{10FFFF}";
+
+        let mut matches = parse_opts(&specs, &["-o", &escape(OsString::from(arg))]);
+        assert_eq!(Some(OsString::from(arg)), matches.opt_str_os("opt"));
+
+        matches = parse_opts(&specs, &["-o", arg]);
+        matches.escaped = false;
+        assert_eq!(Some(OsString::from(arg)), matches.opt_str_os("opt"));
+    }
+
+    /// Parses `args` with an app that has an option, an argument and trailing arguments, all of
+    /// them validated to not be empty.
+    fn parse_os(args: Vec<OsString>) -> anyhow::Result<Matches> {
+        let not_empty =
+            |value: &str| if value.is_empty() { Err("empty".to_owned()) } else { Ok(()) };
+        let builder = Builder::from_args("Test", "1.0", iter::once("p".into()).chain(args))
+            .optopt("o", "opt", "", "")
+            .posarg("arg", "")
+            .trailarg("rest", 0, usize::MAX, "")
+            .validate("opt", not_empty)
+            .validate("arg", not_empty);
+        match builder.parse()? {
+            Outcome::Matches(matches) => Ok(matches),
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_matches_escape_range_text() {
+        let value = |s: &str| OsString::from(format!("{}\u{10FFFF}", s));
+        let matches = parse_os(vec!["-o".into(), value("o"), value("a"), value("b")]).unwrap();
+        assert_eq!(Some("o\u{10FFFF}".to_owned()), matches.opt_str("opt"));
+        assert_eq!(vec!["o\u{10FFFF}".to_owned()], matches.opt_strs("opt"));
+        assert_eq!(Some(value("o")), matches.opt_str_os("opt"));
+        assert_eq!("a\u{10FFFF}", matches.arg_pos("arg"));
+        assert_eq!("a\u{10FFFF}", matches.arg_pos_get::<String>("arg").unwrap());
+        assert_eq!(value("a"), matches.arg_pos_os("arg"));
+        assert_eq!(["b\u{10FFFF}".to_owned()], matches.arg_trail());
+        assert_eq!(vec![value("b")], matches.arg_trail_os());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_matches_invalid_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let value = |s: &str| OsString::from_vec([s.as_bytes(), b"\xff"].concat());
+        let matches = parse_os(vec!["x".into(), value("b")]).unwrap();
+        assert_eq!(["b\u{FFFD}".to_owned()], matches.arg_trail());
+        assert_eq!(vec![value("b")], matches.arg_trail_os());
+        assert_eq!(
+            bad_usage!("Invalid UTF-8 in value `b\u{FFFD}` for argument `rest`"),
+            matches.arg_trail_get::<String>().unwrap_err()
+        );
+
+        let err = parse_os(vec!["-o".into(), value("o"), "x".into()]).unwrap_err();
+        assert_eq!(
+            "Invalid UTF-8 in value `o\u{FFFD}` for option --opt",
+            err.downcast_ref::<UsageError>().unwrap().to_string()
+        );
+        let err = parse_os(vec![value("a")]).unwrap_err();
+        assert_eq!(
+            "Invalid UTF-8 in value `a\u{FFFD}` for argument `arg`",
+            err.downcast_ref::<UsageError>().unwrap().to_string()
+        );
+    }
+}
@@ -13,6 +13,6 @@
 pub use crate::errors::{UsageError, bad_usage};
 pub use crate::licenses::License;
 #[cfg(feature = "env_logger")]
-pub use crate::run::init_env_logger;
-pub use crate::{Builder, Matches, app, tokio_app};
+pub use crate::run::{init_env_logger, init_env_logger_with_verbosity};
+pub use crate::{Builder, DuplicateKeys, Matches, Outcome, app, tokio_app};
 pub use anyhow::{Result, anyhow, bail};
@@ -0,0 +1,276 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Expansion of `@file` arguments into the contents of response files.
+
+use crate::Builder;
+use crate::errors::{UsageError, bad_usage};
+use crate::os_args;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Splits the `contents` of the response file called `name` into arguments.
+///
+/// Arguments are separated by whitespace.  Single quotes preserve the literal value of all
+/// characters they enclose.  Double quotes do the same except for backslashes, which escape the
+/// following `"` or `\`.  Outside of quotes, a backslash escapes any following character and a
+/// `#` at the start of an argument begins a comment that runs until the end of the line.
+fn split(name: &str, contents: &str) -> Result<Vec<String>, UsageError> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = contents.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => args.extend(arg.take()),
+
+            '#' if arg.is_none() => {
+                for ch in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => arg.push(ch),
+                        None => return Err(bad_usage!("Unterminated quote in {}", name)),
+                    }
+                }
+            }
+
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch) if ch == '"' || ch == '\\' => arg.push(ch),
+                            Some(ch) => {
+                                arg.push('\\');
+                                arg.push(ch);
+                            }
+                            None => return Err(bad_usage!("Unterminated quote in {}", name)),
+                        },
+                        Some(ch) => arg.push(ch),
+                        None => return Err(bad_usage!("Unterminated quote in {}", name)),
+                    }
+                }
+            }
+
+            '\\' => {
+                let arg = arg.get_or_insert_with(String::new);
+                arg.push(chars.next().unwrap_or('\\'));
+            }
+
+            ch => arg.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Appends `args` to `expanded` after replacing every `@path` argument with the arguments in the
+/// file at `path`, or in `stdin` if `path` is `-`.  Reading from stdin is an error if `stdin` is
+/// not available.
+///
+/// If `escaped` is true, `args` are escaped by `os_args::escape` and the arguments read from the
+/// response files are escaped in the same way.
+///
+/// `active` contains the response files being expanded to detect inclusion cycles.
+fn expand_into(
+    args: Vec<String>,
+    escaped: bool,
+    mut stdin: Option<&mut (dyn Read + '_)>,
+    active: &mut Vec<PathBuf>,
+    expanded: &mut Vec<String>,
+) -> Result<(), UsageError> {
+    for arg in args {
+        let path = match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+
+        let (name, id, contents) = if path == "-" {
+            let Some(stdin) = stdin.as_deref_mut() else {
+                return Err(bad_usage!("Cannot read response file from stdin: not available"));
+            };
+            let mut contents = String::new();
+            if let Err(e) = stdin.read_to_string(&mut contents) {
+                return Err(bad_usage!("Cannot read response file from stdin: {}", e));
+            }
+            ("stdin".to_owned(), PathBuf::from("-"), contents)
+        } else {
+            let path = PathBuf::from(if escaped { os_args::unescape(path) } else { path.into() });
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    return Err(bad_usage!("Cannot read response file {}: {}", path.display(), e));
+                }
+            };
+            let id = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            (format!("response file {}", path.display()), id, contents)
+        };
+
+        if active.contains(&id) {
+            return Err(bad_usage!("Recursive inclusion of {}", name));
+        }
+        active.push(id);
+        let mut args = split(&name, &contents)?;
+        if escaped {
+            args = args.into_iter().map(|arg| os_args::escape(arg.into())).collect();
+        }
+        expand_into(args, escaped, stdin.as_deref_mut(), active, expanded)?;
+        active.pop();
+    }
+    Ok(())
+}
+
+/// Replaces every `@path` argument in `args` with the arguments in the file at `path`, or in
+/// `stdin` if `path` is `-`, recursively.  `args` are escaped by `os_args::escape` if `escaped`
+/// is true.
+pub(crate) fn expand(
+    args: Vec<String>,
+    escaped: bool,
+    stdin: Option<&mut dyn Read>,
+) -> Result<Vec<String>, UsageError> {
+    let mut expanded = vec![];
+    expand_into(args, escaped, stdin, &mut vec![], &mut expanded)?;
+    Ok(expanded)
+}
+
+impl Builder {
+    /// Makes the application replace every `@path` argument with the arguments listed in the
+    /// file at `path`, or in stdin if `path` is `-`, before processing the command line.
+    ///
+    /// Arguments in a response file are separated by whitespace and can be quoted with single or
+    /// double quotes.  Lines starting with `#` are comments.  Response files can reference other
+    /// response files.
+    pub fn response_files(mut self) -> Self {
+        self.app.response_files = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::TestDir;
+    use std::path::Path;
+
+    /// Returns the `@path` argument that references `path`.
+    fn at(path: &Path) -> String {
+        format!("@{}", path.display())
+    }
+
+    /// Converts a slice of string literals into a vector of strings.
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_whitespace_and_comments() {
+        assert_eq!(
+            strings(&["a", "b", "c#d", "e"]),
+            split("x", "  a\tb\n# comment @f\nc#d # trailing\n\n e").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_split_quotes() {
+        assert_eq!(
+            strings(&["a b", "c\\d", "e\"f\\g\\h", "", "i j'k", "#l"]),
+            split("x", r#"'a b' 'c\d' "e\"f\\g\h" "" i" "j"'"k \#l"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_split_unterminated_quote() {
+        assert_eq!(
+            bad_usage!("Unterminated quote in some file"),
+            split("some file", "a 'b c").unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Unterminated quote in some file"),
+            split("some file", "a \"b c\\").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_expand_no_response_files() {
+        let args = strings(&["a", "@", "b"]);
+        assert_eq!(args.clone(), expand(args, false, None).unwrap());
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let tmp = TestDir::new("response-files-recursive");
+        let dir = tmp.path();
+        let inner = dir.join("inner");
+        fs::write(&inner, "c d").unwrap();
+        let outer = dir.join("outer");
+        fs::write(&outer, format!("b '{}' e\n", at(&inner))).unwrap();
+        assert_eq!(
+            strings(&["a", "b", "c", "d", "e", "f", "c", "d"]),
+            expand(strings(&["a", &at(&outer), "f", &at(&inner)]), false, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expand_stdin() {
+        let mut stdin = "b 'c d'\n".as_bytes();
+        assert_eq!(
+            strings(&["a", "b", "c d", "e"]),
+            expand(strings(&["a", "@-", "e"]), false, Some(&mut stdin)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expand_stdin_not_available() {
+        assert_eq!(
+            bad_usage!("Cannot read response file from stdin: not available"),
+            expand(strings(&["a", "@-"]), false, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_expand_cycle() {
+        let tmp = TestDir::new("response-files-cycle");
+        let dir = tmp.path();
+        let first = dir.join("first");
+        let second = dir.join("second");
+        fs::write(&first, at(&second)).unwrap();
+        fs::write(&second, format!("a {}", at(&first))).unwrap();
+        assert_eq!(
+            bad_usage!("Recursive inclusion of response file {}", first.display()),
+            expand(vec![at(&first)], false, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_expand_missing_file() {
+        let tmp = TestDir::new("response-files-missing");
+        let dir = tmp.path();
+        let missing = dir.join("missing");
+        let err = expand(vec![at(&missing)], false, None).unwrap_err();
+        assert!(
+            err.message.starts_with(&format!("Cannot read response file {}: ", missing.display())),
+            "Got: {}",
+            err
+        );
+    }
+}
@@ -10,21 +10,31 @@
 
 //! Execution logic.
 
-use crate::{App, Arguments, License, Matches, UsageError};
+use crate::errors::bad_usage;
+use crate::fallbacks;
+use crate::opts::{self, OptSpec};
+#[cfg(feature = "env_logger")]
+use crate::verbosity;
+use crate::{
+    App, Arguments, Constraint, Inherited, License, Matches, OptionMatches, Subcommand, UsageError,
+    console, constraints, os_args, response_files, subcommands, validators,
+};
 use anyhow::Result;
-use getopts::Options;
+use getopts::{Options, ParsingStyle};
 use std::env;
 use std::error::Error;
-use std::io;
-use std::path::Path;
+use std::ffi::OsString;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
 
-/// Consumes and returns the program name from `env::Args`.
+/// Consumes and returns the program name from the first of the command line `args`.
 ///
 /// If the program name cannot be obtained, return `default_name` instead.
-pub(crate) fn program_name<S: Into<String>>(
-    mut args: env::Args,
-    default_name: S,
-) -> (String, env::Args) {
+pub(crate) fn program_name<I, S>(mut args: I, default_name: S) -> (String, I)
+where
+    I: Iterator<Item = OsString>,
+    S: Into<String>,
+{
     let name = match args.next() {
         Some(arg0) => match Path::new(&arg0).file_stem() {
             Some(basename) => match basename.to_str() {
@@ -38,55 +48,85 @@ pub(crate) fn program_name<S: Into<String>>(
     (name, args)
 }
 
-/// Prints usage information for program `name` with `opts` following the GNU Standards format.
+/// Formats usage information for `command` with `opts` following the GNU Standards format.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn help(
     stylized_name: &str,
-    program_name: &str,
+    command: &str,
     bugs: Option<&str>,
     homepage: Option<&str>,
     extra_help: Option<fn(&mut dyn io::Write) -> io::Result<()>>,
     opts: &Options,
+    global_opts: &Options,
+    constraints: &[Constraint],
     args: &Arguments,
-) {
-    let mut brief = format!("Usage: {} [options]", program_name);
+    subcommands: &[Subcommand],
+    external_subcommands: Option<Vec<String>>,
+) -> String {
+    let mut brief = format!("Usage: {} [options]", command);
+    for constraint_brief in constraints.iter().filter_map(Constraint::brief) {
+        brief.push(' ');
+        brief.push_str(&constraint_brief);
+    }
     let args_usage = args.brief();
     if !args_usage.is_empty() {
         brief.push(' ');
         brief.push_str(&args_usage);
     }
+    let subcommands_usage = subcommands::brief(subcommands, external_subcommands.is_some());
+    if !subcommands_usage.is_empty() {
+        brief.push(' ');
+        brief.push_str(&subcommands_usage);
+    }
 
-    println!("{}", opts.usage(&brief));
+    let mut text = format!("{}\n", opts.usage(&brief));
+    let global_opts_usage =
+        global_opts.usage_with_format(|rows| rows.collect::<Vec<_>>().join("\n"));
+    if !global_opts_usage.is_empty() {
+        text.push_str(&format!("Global options:\n{}\n\n", global_opts_usage));
+    }
     if !args_usage.is_empty() {
-        println!("{}", args.usage());
+        text.push_str(&format!("{}\n", args.usage()));
+    }
+    if !subcommands.is_empty() {
+        text.push_str(&format!("{}\n", subcommands::usage(subcommands)));
+    }
+    let external_usage = subcommands::external_usage(&external_subcommands.unwrap_or_default());
+    if !external_usage.is_empty() {
+        text.push_str(&format!("{}\n", external_usage));
     }
 
     if let Some(extra_help) = extra_help {
-        let _ = extra_help(&mut io::stdout().lock());
-        println!();
+        let mut output = vec![];
+        let _ = extra_help(&mut output);
+        text.push_str(&String::from_utf8_lossy(&output));
+        text.push('\n');
     }
 
     if let Some(bugs) = bugs {
-        println!("Report bugs to: {}", bugs);
+        text.push_str(&format!("Report bugs to: {}\n", bugs));
     }
     if let Some(homepage) = homepage {
-        println!("{} home page: {}", stylized_name, homepage);
+        text.push_str(&format!("{} home page: {}\n", stylized_name, homepage));
     }
+    text
 }
 
-/// Prints version information following the GNU Standards format.
+/// Formats version information following the GNU Standards format.
 pub(crate) fn version(
     stylized_name: &str,
     version: &str,
     copyright: Option<&str>,
     license: Option<License>,
-) {
-    println!("{} {}", stylized_name, version);
+) -> String {
+    let mut text = format!("{} {}\n", stylized_name, version);
     if let Some(copyright) = copyright {
-        println!("{}", copyright);
+        text.push_str(&format!("{}\n", copyright));
     }
     if let Some(license) = license {
-        println!("License {}", license)
+        text.push_str(&format!("License {}\n", license));
     }
+    text
 }
 
 /// Initialize the logger with settings that match traditional apps.
@@ -97,9 +137,16 @@ pub(crate) fn version(
 /// more suited for log files, not human consumption.
 #[cfg(feature = "env_logger")]
 pub fn init_env_logger<P: Into<String>>(program_name: P) {
-    use std::io::Write;
+    init_env_logger_with_verbosity(program_name, 0)
+}
+
+/// Same as `init_env_logger` but selects the default log level from the net `verbosity` level
+/// as returned by `Matches::verbosity`.  `RUST_LOG` takes precedence if set.
+#[cfg(feature = "env_logger")]
+pub fn init_env_logger_with_verbosity<P: Into<String>>(program_name: P, verbosity: i32) {
+    let filter = verbosity::default_filter(verbosity);
     let mut builder =
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(filter));
     {
         let program_name = program_name.into();
         builder.format(move |buf, record| {
@@ -109,45 +156,250 @@ pub fn init_env_logger<P: Into<String>>(program_name: P) {
     builder.init()
 }
 
+/// Result of processing the command line of a single command.
+pub(crate) enum Parsed {
+    /// Help was requested and the program must print this text and exit without error.
+    Help(String),
+
+    /// The version was requested and the program must print this text and exit without error.
+    Version(String),
+
+    /// The command line was valid and the main function must run with these matches.
+    Main(Matches),
+
+    /// The command line selected a subcommand, which must process the remaining arguments.
+    Subcommand {
+        /// Index of the selected subcommand.
+        index: usize,
+
+        /// Full name of the selected subcommand for usage messages.
+        command: String,
+
+        /// Arguments that the subcommand must process.
+        env_args: Vec<String>,
+
+        /// State that the subcommand inherits from its parents.
+        inherited: Inherited,
+    },
+
+    /// The command line selected the external subcommand at the given path, which must run with
+    /// the remaining arguments.
+    External(PathBuf, Vec<String>),
+}
+
+/// Computes the state that a subcommand inherits from a command that defines `opts` and accepts
+/// `global_opts` from its own parents, all of them subject to `constraints`.  `matches` contains
+/// the option matches of the parents and `opt_matches` the option matches of the command, which
+/// start at `offset` and span `num_args`.
+fn inherit(
+    opts: Vec<OptSpec>,
+    global_opts: Vec<OptSpec>,
+    constraints: Vec<Constraint>,
+    mut matches: Vec<(usize, OptionMatches)>,
+    offset: usize,
+    opt_matches: OptionMatches,
+    num_args: usize,
+) -> Inherited {
+    matches.push((offset, opt_matches));
+    Inherited {
+        opts: global_opts.into_iter().chain(opts.into_iter().filter(|o| !o.is("help"))).collect(),
+        constraints,
+        matches,
+        offset: offset + num_args,
+    }
+}
+
 /// Handles non-configurable options before program start (such as `--help` and `--version`).
+///
+/// Response files can only be read from stdin if `stdin` is available.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn pre_run(
     app: &App,
-    opts: Options,
+    mut opts: Vec<OptSpec>,
     args: Arguments,
-    env_args: env::Args,
-) -> Result<Option<Matches>> {
-    let mut opt_matches = opts.parse(env_args)?;
+    constraints: Vec<Constraint>,
+    subcommands: &[Subcommand],
+    inherited: Inherited,
+    env_args: Vec<String>,
+    stdin: Option<&mut dyn io::Read>,
+) -> Result<Parsed> {
+    // Options defined by the parent commands are accepted here too unless they are shadowed by
+    // options with the same name.
+    let Inherited {
+        opts: global_opts,
+        constraints: global_constraints,
+        matches: parent_matches,
+        offset,
+    } = inherited;
+    for spec in &mut opts {
+        spec.level = parent_matches.len();
+    }
+    let global_opts = global_opts
+        .into_iter()
+        .filter(|global| !opts.iter().any(|local| local.clashes(global)))
+        .collect::<Vec<OptSpec>>();
+
+    let has_subcommands = !subcommands.is_empty() || app.external_subcommands;
+    let mut all_opts = opts::to_getopts(opts.iter().chain(global_opts.iter()));
+    if has_subcommands {
+        all_opts.parsing_style(ParsingStyle::StopAtFirstFree);
+    }
+
+    // Arguments arrive escaped from the builder of the outermost command and, unless the app
+    // asked for them as given, must be converted back to plain strings exactly once.
+    let env_args = if !app.os_args && parent_matches.is_empty() {
+        os_args::to_utf8(env_args)?
+    } else {
+        env_args
+    };
+
+    let env_args = if app.response_files {
+        response_files::expand(env_args, app.os_args, stdin)?
+    } else {
+        env_args
+    };
+
+    // In multicall mode, the program name selects the subcommand to run and all arguments belong
+    // to it.
+    if app.multicall
+        && let Some(index) = subcommands.iter().position(|s| s.name == app.program_name)
+    {
+        let opt_matches = parse_opts(&all_opts, vec![])?;
+        return Ok(Parsed::Subcommand {
+            index,
+            command: app.program_name.clone(),
+            env_args,
+            inherited: inherit(
+                opts,
+                global_opts,
+                global_constraints.into_iter().chain(constraints).collect(),
+                parent_matches,
+                offset,
+                opt_matches,
+                0,
+            ),
+        });
+    }
+
+    let num_args = env_args.len();
+    let mut opt_matches = parse_opts(&all_opts, env_args)?;
 
     if opt_matches.opt_present("help") {
-        help(
+        let external_subcommands = if app.external_subcommands {
+            let path = env::var_os("PATH").unwrap_or_default();
+            let externals =
+                subcommands::find_externals(&path, &subcommands::external_prefix(&app.command));
+            Some(
+                externals
+                    .into_keys()
+                    .filter(|name| !subcommands.iter().any(|s| s.name == name))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        let text = help(
             app.stylized_name,
-            &app.program_name,
+            &app.command,
             app.bugs,
             app.homepage,
             app.extra_help,
-            &opts,
+            &opts::to_help(&opts),
+            &opts::to_help(&global_opts),
+            &constraints,
             &args,
+            subcommands,
+            external_subcommands,
         );
-        return Ok(None);
+        return Ok(Parsed::Help(text));
     }
 
     if opt_matches.opt_present("version") {
-        version(app.stylized_name, app.version, app.copyright, app.license);
-        return Ok(None);
+        let text = version(app.stylized_name, app.version, app.copyright, app.license);
+        return Ok(Parsed::Version(text));
     }
 
-    let arg_matches = args.parse(opt_matches.free.split_off(0))?;
+    let mut free = opt_matches.free.split_off(0);
+    if has_subcommands && !free.is_empty() {
+        let name = free.remove(0);
+        let name =
+            if app.os_args { os_args::decode(&name).unwrap_or_else(|lossy| lossy) } else { name };
+        let index = match subcommands.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                if app.external_subcommands {
+                    let path = env::var_os("PATH").unwrap_or_default();
+                    let prefix = subcommands::external_prefix(&app.command);
+                    if let Some(path) = subcommands::find_external(&path, &prefix, &name) {
+                        return Ok(Parsed::External(path, free));
+                    }
+                }
+                return Err(bad_usage!("Unknown command `{}`", name).into());
+            }
+        };
 
-    #[cfg(feature = "env_logger")]
-    if app.init_env_logger {
-        init_env_logger(&app.program_name);
+        return Ok(Parsed::Subcommand {
+            index,
+            command: format!("{} {}", app.command, name),
+            env_args: free,
+            inherited: inherit(
+                opts,
+                global_opts,
+                global_constraints.into_iter().chain(constraints).collect(),
+                parent_matches,
+                offset,
+                opt_matches,
+                num_args,
+            ),
+        });
     }
 
-    Ok(Some(Matches {
+    let arg_matches = args.parse(os_args::to_os(free, app.os_args))?;
+
+    let mut all_matches = parent_matches;
+    all_matches.push((offset, opt_matches));
+    let mut matches = Matches {
         program_name: app.program_name.clone(),
-        opts: opt_matches,
+        opts: all_matches,
         args: arg_matches,
-    }))
+        fallbacks: vec![],
+        specs: opts.iter().chain(global_opts.iter()).cloned().collect(),
+        escaped: app.os_args,
+    };
+    let files = match app.config.as_ref() {
+        Some(config) => config.load(matches.opt_str_os("config"), |var| env::var_os(var))?,
+        None => vec![],
+    };
+    matches.fallbacks = fallbacks::resolve(
+        opts.iter().chain(global_opts.iter()),
+        &matches,
+        |var| env::var_os(var),
+        &files,
+    )?;
+    let all_constraints = global_constraints.into_iter().chain(constraints).collect::<Vec<_>>();
+    constraints::check(&all_constraints, &mut matches)?;
+    for spec in opts.iter().chain(global_opts.iter()) {
+        for (what, value) in matches.opt_strs_described(spec.name())? {
+            validators::check(&spec.validators, &what, &value)?;
+        }
+    }
+
+    Ok(Parsed::Main(matches))
+}
+
+/// Parses `args` with `opts`, reporting parsing failures as usage errors.
+fn parse_opts(opts: &Options, args: Vec<String>) -> Result<OptionMatches, UsageError> {
+    opts.parse(args).map_err(|e| bad_usage!("{}", e))
+}
+
+/// Initializes the logger, if requested by the app, right before running the main function with
+/// the valid command line in `matches`.
+#[cfg(feature = "env_logger")]
+pub(crate) fn init_logger(app: &App, matches: &Matches) {
+    if app.init_env_logger {
+        let verbosity = if app.verbosity_flags { matches.verbosity() } else { 0 };
+        init_env_logger_with_verbosity(&app.program_name, verbosity);
+    }
 }
 
 /// Prints a usage error `e` to stderr.
@@ -156,14 +408,16 @@ pub(crate) fn pre_run(
 /// help.  This does not print the full help message by design as that would be distracting, yet
 /// that's what other option parsing libraries like to do.
 pub(crate) fn print_usage_error<E: Error>(app: &App, e: E) {
-    eprintln!("Usage error: {}", e);
-    match app.manpage {
-        Some((page, section)) => eprintln!(
+    let mut stderr = console::stderr();
+    let _ = writeln!(stderr, "Usage error: {}", e);
+    let _ = match app.manpage {
+        Some((page, section)) => writeln!(
+            stderr,
             "Type `{} --help` or `man {} {}` for more information",
-            app.program_name, section, page
+            app.command, section, page
         ),
-        None => eprintln!("Type `{} --help` for more information", app.program_name),
-    }
+        None => writeln!(stderr, "Type `{} --help` for more information", app.command),
+    };
 }
 
 /// Handles the error returned from the app's main function, printing it to the console in the
@@ -176,7 +430,7 @@ pub(crate) fn handle_error(app: &App, e: anyhow::Error) -> i32 {
         print_usage_error(app, e);
         2
     } else {
-        eprintln!("{}: {}", app.program_name, e);
+        let _ = writeln!(console::stderr(), "{}: {}", app.program_name, e);
         1
     }
 }
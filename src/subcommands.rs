@@ -0,0 +1,444 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Subcommand registration and dispatching.
+
+use crate::args::{COL2_START, COL2_WIDTH, format_two_columns};
+use crate::{App, Builder, Constraint, Matches, OptSpec, OptionMatches};
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process;
+
+/// Future returned by an async main function once boxed.
+pub(crate) type MainFuture<'a> = Pin<Box<dyn Future<Output = Result<i32>> + 'a>>;
+
+/// Entry point of the application or of one of its subcommands.
+pub(crate) enum Main<'a> {
+    /// A main function that runs synchronously.
+    Sync(fn(Matches) -> Result<i32>),
+
+    /// A main function that returns a future and that can only be awaited from `run_async`.
+    Async(Box<dyn Fn(Matches) -> MainFuture<'a> + 'a>),
+}
+
+impl Main<'_> {
+    /// Runs the main function with `matches` outside of an async context.
+    pub(crate) fn call_sync(&self, matches: Matches) -> Result<i32> {
+        match self {
+            Main::Sync(main) => main(matches),
+            Main::Async(_) => Err(anyhow!("Async main functions require run_async")),
+        }
+    }
+
+    /// Runs the main function with `matches`.
+    pub(crate) async fn call(&self, matches: Matches) -> Result<i32> {
+        match self {
+            Main::Sync(main) => main(matches),
+            Main::Async(main) => main(matches).await,
+        }
+    }
+}
+
+/// State that a subcommand inherits from the commands that precede it in the command line.
+#[derive(Default)]
+pub(crate) struct Inherited {
+    /// Options defined by the parent commands, which are also accepted by the subcommand.
+    pub(crate) opts: Vec<OptSpec>,
+
+    /// Constraints on the options of the parent commands, which also apply to the subcommand.
+    pub(crate) constraints: Vec<Constraint>,
+
+    /// Option matches of the parent commands, from the outermost, along with the offset to apply
+    /// to their option positions.
+    pub(crate) matches: Vec<(usize, OptionMatches)>,
+
+    /// Offset to apply to the option positions of the subcommand.
+    pub(crate) offset: usize,
+}
+
+/// Definition of a subcommand.
+pub(crate) struct Subcommand {
+    /// Name of the subcommand as typed by the user.
+    pub(crate) name: &'static str,
+
+    /// Description of the subcommand for help messages.
+    description: &'static str,
+
+    /// Function to configure the options and arguments of the subcommand.
+    setup: fn(Builder) -> Builder,
+
+    /// Entry point of the subcommand.
+    pub(crate) main: Main<'static>,
+}
+
+impl Subcommand {
+    /// Creates the builder for this subcommand, which inherits the metadata of the parent `app`
+    /// and the `inherited` state of the parent commands, and will process the `env_args` that
+    /// followed the subcommand name.  `command` is the full name of the subcommand as shown in
+    /// usage messages.
+    pub(crate) fn builder(
+        &self,
+        app: &App,
+        command: String,
+        env_args: Vec<String>,
+        inherited: Inherited,
+    ) -> Builder {
+        let mut app = app.clone();
+        app.command = command;
+        app.extra_help = None;
+        app.external_subcommands = false;
+        app.multicall = false;
+        app.response_files = false;
+        if let Some(config) = app.config.as_mut() {
+            config.section.push(self.name.to_owned());
+        }
+        (self.setup)(Builder::with_app(app, env_args, inherited))
+    }
+}
+
+/// Returns the prefix that the executables of the external subcommands of `command` must have.
+pub(crate) fn external_prefix(command: &str) -> String {
+    format!("{}-", command.replace(' ', "-"))
+}
+
+/// Returns true if `path` looks like an executable file.
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        #[cfg(unix)]
+        Ok(metadata) => {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        }
+        #[cfg(not(unix))]
+        Ok(metadata) => metadata.is_file(),
+        Err(_) => false,
+    }
+}
+
+/// Looks for the executable of the external subcommand `name` in the directories listed in `path`,
+/// which has the same format as the `PATH` environment variable.
+pub(crate) fn find_external(path: &OsStr, prefix: &str, name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}{}", prefix, name, env::consts::EXE_SUFFIX);
+    env::split_paths(path).map(|dir| dir.join(&file_name)).find(|path| is_executable(path))
+}
+
+/// Scans the directories listed in `path`, which has the same format as the `PATH` environment
+/// variable, for the executables of all external subcommands.
+///
+/// Returns a mapping of subcommand names to the executables that implement them.  If the same
+/// name appears more than once, the first occurrence wins.
+pub(crate) fn find_externals(path: &OsStr, prefix: &str) -> BTreeMap<String, PathBuf> {
+    let mut externals = BTreeMap::new();
+    for dir in env::split_paths(path) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let name = match file_name
+                .to_str()
+                .and_then(|s| s.strip_prefix(prefix))
+                .and_then(|s| s.strip_suffix(env::consts::EXE_SUFFIX))
+            {
+                Some(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+            if !externals.contains_key(name) && is_executable(&entry.path()) {
+                externals.insert(name.to_owned(), entry.path());
+            }
+        }
+    }
+    externals
+}
+
+/// Executes the external subcommand at `path` with `args` and returns its exit code.
+pub(crate) fn run_external(path: &Path, args: Vec<OsString>) -> Result<i32> {
+    let status = process::Command::new(path)
+        .args(args)
+        .status()
+        .map_err(|e| anyhow!("Cannot execute {}: {}", path.display(), e))?;
+    match status.code() {
+        Some(code) => Ok(code),
+        None => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::ExitStatusExt;
+                if let Some(signal) = status.signal() {
+                    return Ok(128 + signal);
+                }
+            }
+            Ok(1)
+        }
+    }
+}
+
+/// Generates a brief description of the subcommands to be used in usage summaries.
+///
+/// `external` indicates whether external subcommands are enabled or not.
+pub(crate) fn brief(subcommands: &[Subcommand], external: bool) -> String {
+    if subcommands.is_empty() && !external { String::new() } else { "command [args]".to_owned() }
+}
+
+/// Generates a multi-line usage message with the details of all subcommands.
+///
+/// The output matches the format of `getopts::Matches::usage`.
+pub(crate) fn usage(subcommands: &[Subcommand]) -> String {
+    if subcommands.is_empty() {
+        return String::new();
+    }
+
+    let mut text = String::from("Commands:\n");
+    for subcommand in subcommands {
+        text += &format_two_columns(
+            format!("    {}", subcommand.name),
+            subcommand.description,
+            COL2_START,
+            COL2_WIDTH,
+        );
+        text.push('\n');
+    }
+    text
+}
+
+/// Generates a multi-line usage message listing the external subcommands in `names`.
+///
+/// The output matches the format of `getopts::Matches::usage`.
+pub(crate) fn external_usage(names: &[String]) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+
+    let mut text = String::from("External commands:\n");
+    for name in names {
+        text += &format!("    {}\n", name);
+    }
+    text
+}
+
+impl Builder {
+    /// Registers a subcommand with `name` and `description`.
+    ///
+    /// When the first argument given to the program matches `name`, the remaining arguments are
+    /// processed according to the options and arguments that `setup` configures on a fresh
+    /// builder, and `main` is executed with the resulting matches.  The main function of the
+    /// parent is only executed if the user does not specify a subcommand.
+    ///
+    /// Subcommands cannot be combined with positional arguments on the same builder.
+    pub fn subcommand(
+        mut self,
+        name: &'static str,
+        description: &'static str,
+        setup: fn(Builder) -> Builder,
+        main: fn(Matches) -> Result<i32>,
+    ) -> Self {
+        self.add_subcommand(Subcommand { name, description, setup, main: Main::Sync(main) });
+        self
+    }
+
+    /// Async version of `subcommand`.  The application must be started with `run_async` for this
+    /// subcommand to be usable: `run` reports an error if the command line selects it.
+    pub fn subcommand_async<F: Future<Output = Result<i32>> + 'static>(
+        mut self,
+        name: &'static str,
+        description: &'static str,
+        setup: fn(Builder) -> Builder,
+        main: fn(Matches) -> F,
+    ) -> Self {
+        let main = Main::Async(Box::new(move |matches| Box::pin(main(matches))));
+        self.add_subcommand(Subcommand { name, description, setup, main });
+        self
+    }
+
+    /// Enables external subcommands.
+    ///
+    /// When the user specifies a subcommand that has not been registered, look for an executable
+    /// named after the program and the subcommand, such as `tool-verb` for `tool verb`, in the
+    /// directories listed in `PATH`.  If found, the executable runs with the remaining arguments
+    /// and its exit code is propagated to the caller.
+    pub fn external_subcommands(mut self) -> Self {
+        assert!(self.args.is_empty(), "Cannot register arguments and subcommands at the same time");
+        self.app.external_subcommands = true;
+        self
+    }
+
+    /// Enables multicall mode, where a single binary provides multiple programs.
+    ///
+    /// In this mode, the subcommands registered in the builder act as applets: if the name of
+    /// the program (the basename of `argv[0]`) matches the name of a subcommand, that subcommand
+    /// runs directly and processes all arguments.  This allows installing the binary as hard or
+    /// symbolic links named after each applet.  If the program name does not match any applet,
+    /// the first argument is treated as the applet name, as with regular subcommands.
+    pub fn multicall(mut self) -> Self {
+        self.app.multicall = true;
+        self
+    }
+
+    /// Adds `subcommand` to the list of known subcommands after sanity-checking it.
+    fn add_subcommand(&mut self, subcommand: Subcommand) {
+        assert!(self.args.is_empty(), "Cannot register arguments and subcommands at the same time");
+        assert!(
+            !self.subcommands.iter().any(|s| s.name == subcommand.name),
+            "Subcommand {} registered more than once",
+            subcommand.name
+        );
+        self.subcommands.push(subcommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::TestDir;
+
+    fn setup(builder: Builder) -> Builder {
+        builder
+    }
+
+    fn main(_matches: Matches) -> Result<i32> {
+        Ok(0)
+    }
+
+    fn subcommand(name: &'static str, description: &'static str) -> Subcommand {
+        Subcommand { name, description, setup, main: Main::Sync(main) }
+    }
+
+    /// Creates an executable file called `name` in `dir`.
+    fn create_executable(dir: &Path, name: &str) {
+        let path = dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+        fs::write(&path, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_external_prefix() {
+        assert_eq!("tool-", external_prefix("tool"));
+        assert_eq!("tool-remote-", external_prefix("tool remote"));
+    }
+
+    #[test]
+    fn test_find_external() {
+        let tmp1 = TestDir::new("find-external-1");
+        let dir1 = tmp1.path();
+        let tmp2 = TestDir::new("find-external-2");
+        let dir2 = tmp2.path();
+        create_executable(dir1, "tool-foo");
+        create_executable(dir2, "tool-foo");
+        create_executable(dir2, "tool-bar");
+        let path = env::join_paths([dir1, dir2]).unwrap();
+
+        assert_eq!(Some(dir1.join("tool-foo")), find_external(&path, "tool-", "foo"));
+        assert_eq!(Some(dir2.join("tool-bar")), find_external(&path, "tool-", "bar"));
+        assert_eq!(None, find_external(&path, "tool-", "baz"));
+        assert_eq!(None, find_external(&path, "other-", "foo"));
+    }
+
+    #[test]
+    fn test_find_externals() {
+        let tmp1 = TestDir::new("find-externals-1");
+        let dir1 = tmp1.path();
+        let tmp2 = TestDir::new("find-externals-2");
+        let dir2 = tmp2.path();
+        create_executable(dir1, "tool-foo");
+        create_executable(dir2, "tool-foo");
+        create_executable(dir2, "tool-bar");
+        create_executable(dir2, "tool-");
+        create_executable(dir2, "other-baz");
+        let path = env::join_paths([dir1, dir2, &dir1.join("missing")]).unwrap();
+
+        let externals = find_externals(&path, "tool-");
+        assert_eq!(
+            vec![("bar", dir2.join("tool-bar")), ("foo", dir1.join("tool-foo"))],
+            externals.iter().map(|(k, v)| (k.as_str(), v.clone())).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_externals_not_executable() {
+        let tmp = TestDir::new("find-externals-not-executable");
+        let dir = tmp.path();
+        fs::write(dir.join("tool-foo"), "").unwrap();
+        assert!(find_externals(dir.as_os_str(), "tool-").is_empty());
+    }
+
+    #[test]
+    fn test_brief_none() {
+        assert_eq!("", brief(&[], false));
+    }
+
+    #[test]
+    fn test_brief_some() {
+        assert_eq!("command [args]", brief(&[subcommand("foo", "irrelevant")], false));
+        assert_eq!("command [args]", brief(&[], true));
+    }
+
+    #[test]
+    fn test_external_usage_none() {
+        assert_eq!("", external_usage(&[]));
+    }
+
+    #[test]
+    fn test_external_usage_some() {
+        assert_eq!(
+            r"External commands:
+    bar
+    foo
+",
+            external_usage(&["bar".to_owned(), "foo".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_usage_none() {
+        assert_eq!("", usage(&[]));
+    }
+
+    #[test]
+    fn test_usage_some() {
+        assert_eq!(
+            r"Commands:
+    first               the first command
+    second-has-a-long-name
+                        the second command
+",
+            usage(&[
+                subcommand("first", "the first command"),
+                subcommand("second-has-a-long-name", "the second command")
+            ])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot register arguments and subcommands at the same time")]
+    fn test_external_subcommands_then_posarg() {
+        let _ = Builder::from_args("Test", "1.0", ["p"]).external_subcommands().posarg("x", "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot register arguments and subcommands at the same time")]
+    fn test_external_subcommands_then_trailarg() {
+        let _ = Builder::from_args("Test", "1.0", ["p"]).external_subcommands().trailarg(
+            "x",
+            0,
+            usize::MAX,
+            "",
+        );
+    }
+}
@@ -0,0 +1,48 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers shared by the unit tests of all modules.
+
+use crate::Matches;
+use crate::args::Arguments;
+use crate::opts::{OptSpec, to_getopts};
+
+/// Parses every one of the `levels` of a command line as if they belonged to nested commands that
+/// accept all of the options in `specs`, and parses the free arguments of the innermost level
+/// with `arguments`.
+pub(crate) fn parse_matches(
+    specs: &[OptSpec],
+    arguments: &Arguments,
+    levels: &[&[&str]],
+) -> Matches {
+    let getopts = to_getopts(specs);
+    let mut offset = 0;
+    let mut opts = vec![];
+    let mut free = vec![];
+    for args in levels {
+        let mut opt_matches = getopts.parse(*args).unwrap();
+        free = opt_matches.free.split_off(0);
+        opts.push((offset, opt_matches));
+        offset += args.len();
+    }
+    Matches {
+        program_name: "irrelevant".to_owned(),
+        opts,
+        args: arguments.parse(free).unwrap(),
+        fallbacks: vec![],
+        specs: specs.to_vec(),
+        escaped: true,
+    }
+}
+
+/// Parses `args` with the options in `specs` as the command line of a command without arguments.
+pub(crate) fn parse_opts(specs: &[OptSpec], args: &[&str]) -> Matches {
+    parse_matches(specs, &Arguments::default(), &[args])
+}
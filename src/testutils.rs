@@ -10,7 +10,14 @@
 
 //! Helper functions to implement integration tests that use golden input and output files.
 
+use crate::{Builder, Matches, console, diff};
+use anyhow::Result;
+#[cfg(feature = "regex")]
+use regex::Regex;
 use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::iter;
 use std::path::{Path, PathBuf};
 use std::process;
 
@@ -29,6 +36,37 @@ pub fn bin_path<P: AsRef<Path>>(name: P) -> PathBuf {
     debug_or_release_dir.join(name).with_extension(env::consts::EXE_EXTENSION)
 }
 
+/// Empty temporary directory for a test that is deleted, along with its contents, when dropped.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// Creates an empty directory for a test called `name`.
+    ///
+    /// The directory name includes `name` and the identifier of the current process so that
+    /// different tests, and concurrent runs of the same test binary, do not interfere.
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("getoptsargs-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create test directory");
+        Self(dir)
+    }
+
+    /// Returns the path to the directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Name of the environment variable that, when set to a value other than `0`, makes checks
+/// rewrite golden files with the actual output instead of comparing against them.
+pub const UPDATE_GOLDEN_ENV: &str = "GETOPTSARGS_UPDATE_GOLDEN";
+
 /// Describes the behavior for one of the output streams (stdout, stderr) connected to a
 /// program.
 pub enum Behavior {
@@ -37,6 +75,204 @@ pub enum Behavior {
 
     /// Expect the contents of the stream to match this string.
     Inline(String),
+
+    /// Expect the contents of the stream to match the contents of this golden file.
+    ///
+    /// If `UPDATE_GOLDEN_ENV` is set, the file is rewritten with the actual contents of the
+    /// stream instead.
+    File(PathBuf),
+
+    /// Expect the contents of the stream to match this regular expression.  The expression can
+    /// match anywhere in the stream unless anchored with `^` and `$`.  Use `Behavior::regex` to
+    /// compile it.
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+
+    /// Expect the contents of the stream to contain this string.
+    Contains(String),
+
+    /// Expect the stream to have as many lines as matchers and each line to satisfy the matcher
+    /// in the same position.
+    Lines(Vec<Matcher>),
+
+    /// Expect the contents of the stream to satisfy this predicate.
+    Predicate(Box<dyn Fn(&str) -> bool>),
+}
+
+/// Compiles the regular expression `pattern`.
+///
+/// # Panics
+///
+/// This function will panic if `pattern` is invalid.
+#[cfg(feature = "regex")]
+fn compile(pattern: &str) -> Regex {
+    match Regex::new(pattern) {
+        Ok(re) => re,
+        Err(e) => panic!("Invalid regular expression {:?}: {}", pattern, e),
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Behavior {
+    /// Creates a `Regex` behavior that expects the stream to match `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pattern` is not a valid regular expression.
+    pub fn regex(pattern: &str) -> Self {
+        Behavior::Regex(compile(pattern))
+    }
+}
+
+/// Describes the expectation for a piece of output, such as a single line in `Behavior::Lines`.
+pub enum Matcher {
+    /// Expect the text to be equal to this string.
+    Exact(String),
+
+    /// Expect the text to match this regular expression.  The expression can match anywhere in
+    /// the text unless anchored with `^` and `$`.  Use `Matcher::regex` to compile it.
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+
+    /// Expect the text to contain this string.
+    Contains(String),
+
+    /// Expect the text to satisfy this predicate.
+    Predicate(Box<dyn Fn(&str) -> bool>),
+}
+
+impl Matcher {
+    /// Creates a `Regex` matcher that expects the text to match `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pattern` is not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Self {
+        Matcher::Regex(compile(pattern))
+    }
+
+    /// Describes the matcher for failure messages.
+    fn describe(&self) -> String {
+        match self {
+            Matcher::Exact(s) => format!("Exact({:?})", s),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(re) => format!("Regex({:?})", re.as_str()),
+            Matcher::Contains(s) => format!("Contains({:?})", s),
+            Matcher::Predicate(_) => "Predicate".to_owned(),
+        }
+    }
+
+    /// Checks `text` against the matcher and explains the mismatch in terms of `what`, which
+    /// names the text, if it does not match.
+    fn check(&self, text: &str, what: &str) -> Result<(), String> {
+        let matched = match self {
+            Matcher::Exact(s) => s == text,
+            #[cfg(feature = "regex")]
+            Matcher::Regex(re) => re.is_match(text),
+            Matcher::Contains(s) => text.contains(s.as_str()),
+            Matcher::Predicate(f) => f(text),
+        };
+        if matched { Ok(()) } else { Err(format!("{} did not match {}", self.describe(), what)) }
+    }
+}
+
+/// Checks every line of `text` against the matcher in the same position of `matchers`.
+fn check_lines(matchers: &[Matcher], text: &str) -> Result<(), String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    if lines.len() != matchers.len() {
+        return Err(format!(
+            "Lines expected {} lines but the output has {}",
+            matchers.len(),
+            lines.len()
+        ));
+    }
+    for (i, (matcher, line)) in matchers.iter().zip(lines).enumerate() {
+        matcher.check(line, &format!("line {} {:?}", i + 1, line))?;
+    }
+    Ok(())
+}
+
+/// Returns true if the `value` of `UPDATE_GOLDEN_ENV` requests updating golden files.
+fn update_golden(value: Option<OsString>) -> bool {
+    match value {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+/// Checks the `actual` contents of a stream against `behavior`, rewriting golden files if
+/// `update` is true.
+///
+/// Returns a unified diff between the expected and actual contents if they do not match, or an
+/// explanation of which matcher failed for behaviors that do not expect exact contents.
+fn verify(behavior: Behavior, actual: &str, update: bool) -> Result<(), String> {
+    let (expected, name) = match behavior {
+        #[cfg(feature = "regex")]
+        Behavior::Regex(re) => return Matcher::Regex(re).check(actual, "the output"),
+        Behavior::Contains(s) => return Matcher::Contains(s).check(actual, "the output"),
+        Behavior::Lines(matchers) => return check_lines(&matchers, actual),
+        Behavior::Predicate(f) => return Matcher::Predicate(f).check(actual, "the output"),
+        Behavior::Null => ("".to_owned(), "expected".to_owned()),
+        Behavior::Inline(s) => (s, "expected".to_owned()),
+        Behavior::File(path) => {
+            let expected = fs::read_to_string(&path);
+            if update {
+                if expected.ok().as_deref() != Some(actual) {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).expect("Failed to create golden directory");
+                    }
+                    fs::write(&path, actual).unwrap_or_else(|e| {
+                        panic!("Failed to update golden file {}: {}", path.display(), e)
+                    });
+                }
+                return Ok(());
+            }
+            let expected = expected.unwrap_or_else(|e| {
+                panic!(
+                    "Failed to read golden file {}: {}; set {}=1 to create it",
+                    path.display(),
+                    e,
+                    UPDATE_GOLDEN_ENV
+                )
+            });
+            (expected, path.display().to_string())
+        }
+    };
+    if expected == actual { Ok(()) } else { Err(diff::unified(&expected, actual, &name, "actual")) }
+}
+
+/// Checks the `code`, `stdout` and `stderr` produced by a program against expectations.
+fn check_output(
+    code: i32,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    exp_code: i32,
+    stdout_behavior: Behavior,
+    stderr_behavior: Behavior,
+) {
+    let update = update_golden(env::var_os(UPDATE_GOLDEN_ENV));
+
+    let stdout = String::from_utf8(stdout).expect("Stdout not is not valid UTF-8");
+    let stderr = String::from_utf8(stderr).expect("Stderr not is not valid UTF-8");
+
+    let mut failures = vec![];
+    if exp_code != code {
+        failures.push(format!("Exit code mismatch: expected {}, got {}", exp_code, code));
+    }
+    if let Err(diff) = verify(stdout_behavior, &stdout, update) {
+        failures.push(format!("stdout mismatch:\n{}", diff));
+    }
+    if let Err(diff) = verify(stderr_behavior, &stderr, update) {
+        failures.push(format!("stderr mismatch:\n{}", diff));
+    }
+
+    if !failures.is_empty() {
+        eprintln!("Exit code: {}", code);
+        eprintln!("stdout:\n{}", stdout);
+        eprintln!("stderr:\n{}", stderr);
+        panic!("{}", failures.join("\n"));
+    }
 }
 
 /// Runs `bin` with arguments `args` and checks its behavior against expectations.
@@ -50,30 +286,247 @@ pub fn check<P: AsRef<Path>>(
     stdout_behavior: Behavior,
     stderr_behavior: Behavior,
 ) {
-    let exp_stdout = match stdout_behavior {
-        Behavior::Null => "".to_owned(),
-        Behavior::Inline(s) => s,
-    };
-
-    let exp_stderr = match stderr_behavior {
-        Behavior::Null => "".to_owned(),
-        Behavior::Inline(s) => s,
-    };
-
     let result = process::Command::new(bin.as_ref())
         .args(args)
         .output()
         .expect("Failed to execute subprocess");
     let code = result.status.code().expect("Subprocess didn't exit cleanly");
-    let stdout = String::from_utf8(result.stdout).expect("Stdout not is not valid UTF-8");
-    let stderr = String::from_utf8(result.stderr).expect("Stderr not is not valid UTF-8");
+    check_output(code, result.stdout, result.stderr, exp_code, stdout_behavior, stderr_behavior);
+}
 
-    if exp_code != code || exp_stdout != stdout || exp_stderr != stderr {
-        eprintln!("Exit code: {}", code);
-        eprintln!("stdout:\n{}", stdout);
-        eprintln!("stderr:\n{}", stderr);
-        assert_eq!(exp_code, code);
-        assert_eq!(exp_stdout, stdout);
-        assert_eq!(exp_stderr, stderr);
+/// Runs the app defined by `setup` and `main` with arguments `args` within the test process and
+/// checks its behavior against expectations, like `check` does for separate binaries.
+///
+/// The app is named `test`, its stylized name is `Test`, and its version is `0.0.0`.
+///
+/// Output is captured from the library and from anything that `main` writes to
+/// `console::stdout` and `console::stderr`.  Output printed by other means, such as with
+/// `println!` or `log`, or by external subcommands, is not captured and does not count towards
+/// the expectations, so an app must use `console` for its output to be verifiable this way.
+///
+/// The app is not isolated from the test process: environment variables used as fallbacks, the
+/// configuration files they locate, the `PATH` searched for external subcommands and the stdin
+/// read by `@-` response files are all those of the test process.  Tests that depend on any of
+/// these must control them themselves or use `check` on a separate binary instead.
+pub fn check_in_process(
+    setup: fn(Builder) -> Builder,
+    main: fn(Matches) -> Result<i32>,
+    args: &[&str],
+    exp_code: i32,
+    stdout_behavior: Behavior,
+    stderr_behavior: Behavior,
+) {
+    let builder =
+        Builder::from_args("Test", "0.0.0", iter::once("test").chain(args.iter().copied()));
+    #[cfg(feature = "env_logger")]
+    let builder = builder.disable_init_env_logger();
+    let builder = setup(builder);
+    let (code, stdout, stderr) = console::capture(|| builder.run(main));
+    check_output(code, stdout, stderr, exp_code, stdout_behavior, stderr_behavior);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::bad_usage;
+    use anyhow::anyhow;
+    use std::io::Write;
+
+    fn app_setup(builder: Builder) -> Builder {
+        builder.optflag("f", "fail", "fail with an error").optopt("", "num", "a number", "N")
+    }
+
+    fn app_main(matches: Matches) -> Result<i32> {
+        if matches.opt_present("fail") {
+            return Err(anyhow!("Failed as requested"));
+        }
+        let num = matches.opt_get::<i32>("num").map_err(|e| bad_usage!("{}", e))?;
+        writeln!(console::stdout(), "num: {:?}", num)?;
+        writeln!(console::stderr(), "done")?;
+        Ok(3)
+    }
+
+    #[test]
+    fn test_test_dir_cleanup() {
+        let path = {
+            let dir = TestDir::new("test-dir-cleanup");
+            fs::write(dir.path().join("file"), "").unwrap();
+            assert!(dir.path().join("file").exists());
+            dir.path().to_owned()
+        };
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_check_in_process_main() {
+        check_in_process(
+            app_setup,
+            app_main,
+            &["--num=5"],
+            3,
+            Behavior::Inline("num: Some(5)\n".to_owned()),
+            Behavior::Inline("done\n".to_owned()),
+        );
+    }
+
+    #[test]
+    fn test_check_in_process_version() {
+        check_in_process(
+            app_setup,
+            app_main,
+            &["--version"],
+            0,
+            Behavior::Inline("Test 0.0.0\n".to_owned()),
+            Behavior::Null,
+        );
+    }
+
+    #[test]
+    fn test_check_in_process_errors() {
+        check_in_process(
+            app_setup,
+            app_main,
+            &["--num=x"],
+            2,
+            Behavior::Null,
+            Behavior::Inline(
+                "Usage error: invalid digit found in string\n\
+                 Type `test --help` for more information\n"
+                    .to_owned(),
+            ),
+        );
+        check_in_process(
+            app_setup,
+            app_main,
+            &["--fail"],
+            1,
+            Behavior::Null,
+            Behavior::Inline("test: Failed as requested\n".to_owned()),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "stdout mismatch:\n--- expected\n+++ actual\n@@ -0,0 +1 @@\n+num: None\n"
+    )]
+    fn test_check_in_process_mismatch() {
+        check_in_process(app_setup, app_main, &[], 3, Behavior::Null, Behavior::Null);
+    }
+
+    #[test]
+    fn test_update_golden() {
+        assert!(!update_golden(None));
+        assert!(!update_golden(Some(OsString::from(""))));
+        assert!(!update_golden(Some(OsString::from("0"))));
+        assert!(update_golden(Some(OsString::from("1"))));
+        assert!(update_golden(Some(OsString::from("yes"))));
+    }
+
+    #[test]
+    fn test_verify_inline() {
+        verify(Behavior::Null, "", false).unwrap();
+        verify(Behavior::Inline("a\nb\n".to_owned()), "a\nb\n", false).unwrap();
+        assert_eq!(
+            "--- expected\n+++ actual\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n",
+            verify(Behavior::Inline("a\nb\n".to_owned()), "a\nc\n", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let dir = TestDir::new("verify-file");
+        let golden = dir.path().join("golden.txt");
+        fs::write(&golden, "a\nb\n").unwrap();
+
+        verify(Behavior::File(golden.clone()), "a\nb\n", false).unwrap();
+        assert_eq!(
+            format!("--- {}\n+++ actual\n@@ -1,2 +1 @@\n a\n-b\n", golden.display()),
+            verify(Behavior::File(golden.clone()), "a\n", false).unwrap_err()
+        );
+        assert_eq!("a\nb\n", fs::read_to_string(&golden).unwrap());
+    }
+
+    #[test]
+    fn test_verify_file_update() {
+        let dir = TestDir::new("verify-file-update");
+        let golden = dir.path().join("subdir/golden.txt");
+
+        verify(Behavior::File(golden.clone()), "new\n", true).unwrap();
+        assert_eq!("new\n", fs::read_to_string(&golden).unwrap());
+
+        verify(Behavior::File(golden.clone()), "newer\n", true).unwrap();
+        assert_eq!("newer\n", fs::read_to_string(&golden).unwrap());
+        verify(Behavior::File(golden.clone()), "newer\n", false).unwrap();
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_verify_regex() {
+        verify(Behavior::regex("^pid [0-9]+\n$"), "pid 1234\n", false).unwrap();
+        verify(Behavior::regex("[0-9]+"), "pid 1234\n", false).unwrap();
+        assert_eq!(
+            "Regex(\"^pid [0-9]+$\") did not match the output",
+            verify(Behavior::regex("^pid [0-9]+$"), "pid x", false).unwrap_err()
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    #[should_panic(expected = "Invalid regular expression \"(\"")]
+    fn test_regex_invalid() {
+        let _ = Matcher::regex("(");
+    }
+
+    #[test]
+    fn test_verify_contains() {
+        verify(Behavior::Contains("usage".to_owned()), "some usage text", false).unwrap();
+        assert_eq!(
+            "Contains(\"error\") did not match the output",
+            verify(Behavior::Contains("error".to_owned()), "some usage text", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_predicate() {
+        let behavior = || Behavior::Predicate(Box::new(|s| s.len() == 3));
+        verify(behavior(), "abc", false).unwrap();
+        assert_eq!(
+            "Predicate did not match the output",
+            verify(behavior(), "abcd", false).unwrap_err()
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_verify_lines() {
+        let behavior = || {
+            Behavior::Lines(vec![
+                Matcher::Exact("Header".to_owned()),
+                Matcher::regex("^Path: /.*$"),
+                Matcher::Contains("done".to_owned()),
+                Matcher::Predicate(Box::new(|s| s.is_empty())),
+            ])
+        };
+        verify(behavior(), "Header\nPath: /tmp/x\nall done!\n\n", false).unwrap();
+        assert_eq!(
+            "Regex(\"^Path: /.*$\") did not match line 2 \"Path: tmp\"",
+            verify(behavior(), "Header\nPath: tmp\nall done!\n\n", false).unwrap_err()
+        );
+        assert_eq!(
+            "Exact(\"Header\") did not match line 1 \"header\"",
+            verify(behavior(), "header\nPath: /\ndone\n\n", false).unwrap_err()
+        );
+        assert_eq!(
+            "Lines expected 4 lines but the output has 2",
+            verify(behavior(), "Header\nPath: /\n", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "set GETOPTSARGS_UPDATE_GOLDEN=1 to create it")]
+    fn test_verify_file_missing() {
+        let dir = TestDir::new("verify-file-missing");
+        let golden = dir.path().join("golden.txt");
+        let _ = verify(Behavior::File(golden), "", false);
     }
 }
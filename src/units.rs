@@ -0,0 +1,365 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Options that take durations and byte sizes in human-friendly formats.
+
+use crate::errors::{UsageError, bad_usage};
+use crate::opts::OptSpec;
+use crate::validators::Validator;
+use crate::{Builder, Matches};
+use getopts::{HasArg, Occur};
+use std::time::Duration;
+
+/// Number of nanoseconds in a second.
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Units accepted in durations along with their length in nanoseconds, from the largest.
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("d", 86_400 * NANOS_PER_SEC),
+    ("h", 3_600 * NANOS_PER_SEC),
+    ("m", 60 * NANOS_PER_SEC),
+    ("s", NANOS_PER_SEC),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Units accepted in byte sizes along with their multipliers, from the largest of each kind.
+/// Units are case-insensitive and can omit the trailing `B`.
+const BYTE_UNITS: &[(&str, u128)] = &[
+    ("PiB", 1 << 50),
+    ("TiB", 1 << 40),
+    ("GiB", 1 << 30),
+    ("MiB", 1 << 20),
+    ("KiB", 1 << 10),
+    ("PB", 1_000_000_000_000_000),
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+/// Multiplies the decimal `number`, which may have a fractional part, by `multiplier`.
+///
+/// The result must be a whole number.
+fn scale(number: &str, multiplier: u128) -> Result<u128, String> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let is_digits = |s: &str| s.chars().all(|ch| ch.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(format!("invalid number `{}`", number));
+    }
+
+    let too_large = || "too large".to_owned();
+    let whole = whole.parse::<u128>().map_err(|_| too_large())?;
+    let mut value = whole.checked_mul(multiplier).ok_or_else(too_large)?;
+    if !fraction.is_empty() {
+        let denominator = u32::try_from(fraction.len())
+            .ok()
+            .and_then(|len| 10u128.checked_pow(len))
+            .ok_or_else(|| "too precise".to_owned())?;
+        let fraction = fraction.parse::<u128>().map_err(|_| too_large())?;
+        let scaled = fraction.checked_mul(multiplier).ok_or_else(too_large)?;
+        if scaled % denominator != 0 {
+            return Err("too precise".to_owned());
+        }
+        value = value.checked_add(scaled / denominator).ok_or_else(too_large)?;
+    }
+    Ok(value)
+}
+
+/// Parses a duration like `1500ms`, `2m30s` or `1.5h`.  A number without a unit is a number of
+/// seconds.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    if value.is_empty() {
+        return Err("empty duration".to_owned());
+    }
+
+    let is_number = |ch: char| ch.is_ascii_digit() || ch == '.';
+    let mut nanos: u128 = 0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let (number, tail) = rest.split_at(rest.find(|ch| !is_number(ch)).unwrap_or(rest.len()));
+        let (unit, tail) = tail.split_at(tail.find(is_number).unwrap_or(tail.len()));
+        let multiplier = match DURATION_UNITS.iter().find(|(name, _)| *name == unit) {
+            Some((_, multiplier)) => *multiplier,
+            None if unit.is_empty() && rest == value => NANOS_PER_SEC,
+            None if unit.is_empty() => return Err(format!("missing unit after `{}`", number)),
+            None => return Err(format!("unknown unit `{}`", unit)),
+        };
+        nanos = nanos.checked_add(scale(number, multiplier)?).ok_or("too large")?;
+        rest = tail;
+    }
+
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).map_err(|_| "too large")?;
+    Ok(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+}
+
+/// Formats `duration` in the format accepted by `parse_duration`, such as `2m30s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let mut nanos = duration.as_nanos();
+    if nanos == 0 {
+        return "0s".to_owned();
+    }
+
+    let mut formatted = String::new();
+    for (name, multiplier) in DURATION_UNITS {
+        if nanos >= *multiplier {
+            formatted.push_str(&format!("{}{}", nanos / multiplier, name));
+            nanos %= multiplier;
+        }
+    }
+    formatted
+}
+
+/// Parses a byte size like `512`, `10KiB` or `1.5G`.
+///
+/// Units with an `i` are powers of 1024 and units without it are powers of 1000.
+pub(crate) fn parse_bytes(value: &str) -> Result<u64, String> {
+    if value.is_empty() {
+        return Err("empty size".to_owned());
+    }
+
+    let (number, unit) =
+        value.split_at(value.find(|ch: char| ch.is_ascii_alphabetic()).unwrap_or(value.len()));
+    let multiplier = if unit.is_empty() {
+        1
+    } else {
+        let matches = |name: &str| {
+            name.eq_ignore_ascii_case(unit)
+                || (name.len() > 1 && name[..name.len() - 1].eq_ignore_ascii_case(unit))
+        };
+        match BYTE_UNITS.iter().find(|(name, _)| matches(name)) {
+            Some((_, multiplier)) => *multiplier,
+            None => return Err(format!("unknown unit `{}`", unit)),
+        }
+    };
+    u64::try_from(scale(number, multiplier)?).map_err(|_| "too large".to_owned())
+}
+
+/// Formats `bytes` in the format accepted by `parse_bytes`, using the largest unit that
+/// represents the value exactly, such as `10KiB`.
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    let bytes = u128::from(bytes);
+    for (name, multiplier) in BYTE_UNITS {
+        if bytes > 0 && bytes % multiplier == 0 {
+            return format!("{}{}", bytes / multiplier, name);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+impl Builder {
+    /// Defines an option that takes a duration like `1500ms`, `2m30s` or `1.5h`, optionally
+    /// with a `default` value to show in the help message.  Use `Matches::opt_duration` to query
+    /// its value.
+    ///
+    /// Durations are sequences of numbers followed by one of the `d`, `h`, `m`, `s`, `ms`, `us`
+    /// or `ns` units.  A number without a unit is a number of seconds.
+    pub fn optduration(
+        mut self,
+        short_name: &str,
+        long_name: &str,
+        desc: &str,
+        hint: &str,
+        default: Option<Duration>,
+    ) -> Self {
+        let mut spec =
+            OptSpec::new(short_name, long_name, desc, hint, HasArg::Yes, Occur::Optional);
+        spec.default = default.map(format_duration);
+        spec.validators.push(Validator::new(|value| parse_duration(value).map(|_| ())));
+        self.opts.push(spec);
+        self
+    }
+
+    /// Defines an option that takes a byte size like `512`, `10KiB` or `1.5G`, optionally with a
+    /// `default` value to show in the help message.  Use `Matches::opt_bytes` to query its value.
+    ///
+    /// Units with an `i`, like `KiB`, are powers of 1024 and units without it, like `K` or `KB`,
+    /// are powers of 1000.  Units are case-insensitive.
+    pub fn optbytes(
+        mut self,
+        short_name: &str,
+        long_name: &str,
+        desc: &str,
+        hint: &str,
+        default: Option<u64>,
+    ) -> Self {
+        let mut spec =
+            OptSpec::new(short_name, long_name, desc, hint, HasArg::Yes, Occur::Optional);
+        spec.default = default.map(format_bytes);
+        spec.validators.push(Validator::new(|value| parse_bytes(value).map(|_| ())));
+        self.opts.push(spec);
+        self
+    }
+}
+
+impl Matches {
+    /// Returns the value of the option `name`, or its default value if not given, converted by
+    /// the `parse` function.
+    fn opt_parse_default<T>(
+        &self,
+        name: &str,
+        parse: fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, UsageError> {
+        let spec = self.spec(name);
+        let (what, value) = match self.opt_str_described(name)? {
+            Some(described) => described,
+            None => match spec.default.clone() {
+                Some(value) => (format!("option {}", spec.display_name()), value),
+                None => return Ok(None),
+            },
+        };
+        match parse(&value) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(bad_usage!("Invalid value `{}` for {}: {}", value, what, e)),
+        }
+    }
+
+    /// Returns the value of the option `name` as a duration, or its default value if not given.
+    ///
+    /// The option can be of any kind that takes an argument, though it should be defined with
+    /// `Builder::optduration` to validate its value early.  Returns a `UsageError` that names the
+    /// option and its value if the value is invalid.
+    pub fn opt_duration(&self, name: &str) -> Result<Option<Duration>, UsageError> {
+        self.opt_parse_default(name, parse_duration)
+    }
+
+    /// Returns the value of the option `name` as a number of bytes, or its default value if not
+    /// given.
+    ///
+    /// The option can be of any kind that takes an argument, though it should be defined with
+    /// `Builder::optbytes` to validate its value early.  Returns a `UsageError` that names the
+    /// option and its value if the value is invalid.
+    pub fn opt_bytes(&self, name: &str) -> Result<Option<u64>, UsageError> {
+        self.opt_parse_default(name, parse_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::parse_opts;
+
+    /// Parses `args` with a `--timeout` duration option that defaults to 30 seconds, a `--size`
+    /// byte size option without a default, and a `--count` option.
+    fn parse(args: &[&str]) -> Matches {
+        let mut timeout = OptSpec::new("", "timeout", "", "", HasArg::Yes, Occur::Optional);
+        timeout.default = Some("30s".to_owned());
+        let specs = [
+            timeout,
+            OptSpec::new("", "size", "", "", HasArg::Yes, Occur::Optional),
+            OptSpec::new("", "count", "", "", HasArg::Yes, Occur::Optional),
+        ];
+        parse_opts(&specs, args)
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Duration::from_secs(0), parse_duration("0").unwrap());
+        assert_eq!(Duration::from_secs(10), parse_duration("10").unwrap());
+        assert_eq!(Duration::from_millis(2500), parse_duration("2.5").unwrap());
+        assert_eq!(Duration::from_millis(1500), parse_duration("1500ms").unwrap());
+        assert_eq!(Duration::from_secs(150), parse_duration("2m30s").unwrap());
+        assert_eq!(Duration::from_secs(5400), parse_duration("1.5h").unwrap());
+        assert_eq!(Duration::from_secs(90000), parse_duration("1d1h").unwrap());
+        assert_eq!(Duration::new(1, 2003), parse_duration("1s2us3ns").unwrap());
+    }
+
+    #[test]
+    fn test_parse_duration_errors() {
+        assert_eq!("empty duration", parse_duration("").unwrap_err());
+        assert_eq!("invalid number `1.2.3`", parse_duration("1.2.3s").unwrap_err());
+        assert_eq!("invalid number ``", parse_duration("s").unwrap_err());
+        assert_eq!("invalid number `.5`", parse_duration(".5s").unwrap_err());
+        assert_eq!("unknown unit `w`", parse_duration("1w").unwrap_err());
+        assert_eq!("unknown unit `-`", parse_duration("-1s").unwrap_err());
+        assert_eq!("missing unit after `30`", parse_duration("1m30").unwrap_err());
+        assert_eq!("too precise", parse_duration("1.5ns").unwrap_err());
+        assert_eq!("too large", parse_duration("99999999999999999999999d").unwrap_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("0s", format_duration(Duration::ZERO));
+        assert_eq!("1s500ms", format_duration(Duration::from_millis(1500)));
+        assert_eq!("2m30s", format_duration(Duration::from_secs(150)));
+        assert_eq!("1d1h", format_duration(Duration::from_secs(90000)));
+        assert_eq!("1s2us3ns", format_duration(Duration::new(1, 2003)));
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(0, parse_bytes("0").unwrap());
+        assert_eq!(512, parse_bytes("512").unwrap());
+        assert_eq!(512, parse_bytes("512B").unwrap());
+        assert_eq!(10240, parse_bytes("10KiB").unwrap());
+        assert_eq!(10240, parse_bytes("10ki").unwrap());
+        assert_eq!(10000, parse_bytes("10k").unwrap());
+        assert_eq!(10000, parse_bytes("10KB").unwrap());
+        assert_eq!(1_500_000_000, parse_bytes("1.5G").unwrap());
+        assert_eq!(1_610_612_736, parse_bytes("1.5GiB").unwrap());
+        assert_eq!(1 << 50, parse_bytes("1PiB").unwrap());
+    }
+
+    #[test]
+    fn test_parse_bytes_errors() {
+        assert_eq!("empty size", parse_bytes("").unwrap_err());
+        assert_eq!("invalid number ``", parse_bytes("K").unwrap_err());
+        assert_eq!("invalid number `-1`", parse_bytes("-1").unwrap_err());
+        assert_eq!("unknown unit `KiBs`", parse_bytes("1KiBs").unwrap_err());
+        assert_eq!("unknown unit `X`", parse_bytes("1X").unwrap_err());
+        assert_eq!("too precise", parse_bytes("1.5").unwrap_err());
+        assert_eq!("too large", parse_bytes("20000PB").unwrap_err());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!("0B", format_bytes(0));
+        assert_eq!("512B", format_bytes(512));
+        assert_eq!("1500B", format_bytes(1500));
+        assert_eq!("10KiB", format_bytes(10240));
+        assert_eq!("10KB", format_bytes(10000));
+        assert_eq!("1536MiB", format_bytes(1_610_612_736));
+        assert_eq!("1500MB", format_bytes(1_500_000_000));
+    }
+
+    #[test]
+    fn test_matches_opt_duration() {
+        let matches = parse(&[]);
+        assert_eq!(Some(Duration::from_secs(30)), matches.opt_duration("timeout").unwrap());
+        assert_eq!(None, matches.opt_duration("count").unwrap());
+
+        let matches = parse(&["--timeout=1m", "--count=5ms"]);
+        assert_eq!(Some(Duration::from_secs(60)), matches.opt_duration("timeout").unwrap());
+        assert_eq!(Some(Duration::from_millis(5)), matches.opt_duration("count").unwrap());
+
+        let matches = parse(&["--timeout=1x"]);
+        assert_eq!(
+            bad_usage!("Invalid value `1x` for option --timeout: unknown unit `x`"),
+            matches.opt_duration("timeout").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_matches_opt_bytes() {
+        let matches = parse(&[]);
+        assert_eq!(None, matches.opt_bytes("size").unwrap());
+
+        let matches = parse(&["--size=4KiB"]);
+        assert_eq!(Some(4096), matches.opt_bytes("size").unwrap());
+
+        let matches = parse(&["--size=4.5"]);
+        assert_eq!(
+            bad_usage!("Invalid value `4.5` for option --size: too precise"),
+            matches.opt_bytes("size").unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Validation of option and argument values.
+
+use crate::Builder;
+use crate::errors::{UsageError, bad_usage};
+use std::fmt;
+use std::rc::Rc;
+
+/// Signature of the functions that check if a value is acceptable.  Returns an error message if
+/// the value is invalid.
+type ValidatorFn = dyn Fn(&str) -> Result<(), String>;
+
+/// A user-provided function that checks if a value is acceptable.
+#[derive(Clone)]
+pub(crate) struct Validator(Rc<ValidatorFn>);
+
+impl Validator {
+    /// Wraps the `validator` function.
+    pub(crate) fn new<F: Fn(&str) -> Result<(), String> + 'static>(validator: F) -> Self {
+        Self(Rc::new(validator))
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Validator")
+    }
+}
+
+/// Checks the `value` of `what` (such as an argument or an option) against all `validators`.
+///
+/// Returns an error that names `what` and the `value` for the first validator that fails.
+pub(crate) fn check<'a, I>(validators: I, what: &str, value: &str) -> Result<(), UsageError>
+where
+    I: IntoIterator<Item = &'a Validator>,
+{
+    for validator in validators {
+        if let Err(e) = (validator.0)(value) {
+            return Err(bad_usage!("Invalid value `{}` for {}: {}", value, what, e));
+        }
+    }
+    Ok(())
+}
+
+impl Builder {
+    /// Attaches a `validator` to the option or argument called `name`.
+    ///
+    /// The validator runs on every value of the option or argument before the main function
+    /// starts and returns an error message if the value is not acceptable, which becomes a
+    /// usage error.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if neither an option nor an argument called `name` are defined.
+    pub fn validate<F>(mut self, name: &str, validator: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        let validator = Validator::new(validator);
+        if let Some(spec) = self.opts.iter_mut().find(|spec| spec.is(name)) {
+            spec.validators.push(validator);
+        } else if self.args.has(name) {
+            self.args.validate(name, validator);
+        } else {
+            panic!("No option or argument '{}' defined", name);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let validators = [
+            Validator::new(|value| {
+                if value.is_empty() { Err("cannot be empty".to_owned()) } else { Ok(()) }
+            }),
+            Validator::new(
+                |value| if value.len() > 3 { Err("too long".to_owned()) } else { Ok(()) },
+            ),
+        ];
+        check(&validators, "argument `x`", "abc").unwrap();
+        assert_eq!(
+            bad_usage!("Invalid value `` for argument `x`: cannot be empty"),
+            check(&validators, "argument `x`", "").unwrap_err()
+        );
+        assert_eq!(
+            bad_usage!("Invalid value `abcd` for option --x: too long"),
+            check(&validators, "option --x", "abcd").unwrap_err()
+        );
+    }
+}
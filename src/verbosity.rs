@@ -0,0 +1,94 @@
+// getoptsargs
+// Copyright 2025 Julio Merino.
+// All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Standard flags to control the verbosity of the program.
+
+use crate::{Builder, Matches};
+
+/// Computes the default log filter for a net `verbosity` level, where 0 shows informational
+/// messages and every step up or down enables or disables one more level of detail.
+#[cfg(feature = "env_logger")]
+pub(crate) fn default_filter(verbosity: i32) -> &'static str {
+    match verbosity {
+        i32::MIN..=-3 => "off",
+        -2 => "error",
+        -1 => "warn",
+        0 => "info",
+        1 => "debug",
+        2.. => "trace",
+    }
+}
+
+impl Builder {
+    /// Defines the `-v/--verbose` and `-q/--quiet` flags, which can be given multiple times to
+    /// increase or decrease the verbosity of the program.  Use `Matches::verbosity` to query the
+    /// net level.
+    ///
+    /// When the env logger is initialized by the runtime, the net level selects its default
+    /// filter: `info` by default, `debug` and `trace` for one and two `-v` flags, and `warn`,
+    /// `error` and `off` for one, two and three `-q` flags.  `RUST_LOG` takes precedence if set.
+    pub fn verbosity_flags(mut self) -> Self {
+        self.app.verbosity_flags = true;
+        self.optflagmulti("v", "verbose", "increase verbosity; can be given multiple times")
+            .optflagmulti("q", "quiet", "decrease verbosity; can be given multiple times")
+    }
+}
+
+impl Matches {
+    /// Returns the net verbosity level requested by the user, which is the number of times that
+    /// `--verbose` was given minus the number of times that `--quiet` was given.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the verbosity flags have not been defined.
+    pub fn verbosity(&self) -> i32 {
+        let verbose = i32::try_from(self.opt_count("verbose")).unwrap_or(i32::MAX);
+        let quiet = i32::try_from(self.opt_count("quiet")).unwrap_or(i32::MAX);
+        verbose.saturating_sub(quiet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opts::OptSpec;
+    use crate::test_helpers::parse_opts;
+    use getopts::{HasArg, Occur};
+
+    /// Parses `args` with the verbosity flags.
+    fn parse(args: &[&str]) -> Matches {
+        let specs = [
+            OptSpec::new("v", "verbose", "", "", HasArg::No, Occur::Multi),
+            OptSpec::new("q", "quiet", "", "", HasArg::No, Occur::Multi),
+        ];
+        parse_opts(&specs, args)
+    }
+
+    #[test]
+    fn test_matches_verbosity() {
+        assert_eq!(0, parse(&[]).verbosity());
+        assert_eq!(2, parse(&["-vv"]).verbosity());
+        assert_eq!(-1, parse(&["--quiet"]).verbosity());
+        assert_eq!(1, parse(&["-v", "-q", "--verbose"]).verbosity());
+    }
+
+    #[cfg(feature = "env_logger")]
+    #[test]
+    fn test_default_filter() {
+        assert_eq!("off", default_filter(-10));
+        assert_eq!("off", default_filter(-3));
+        assert_eq!("error", default_filter(-2));
+        assert_eq!("warn", default_filter(-1));
+        assert_eq!("info", default_filter(0));
+        assert_eq!("debug", default_filter(1));
+        assert_eq!("trace", default_filter(2));
+        assert_eq!("trace", default_filter(10));
+    }
+}
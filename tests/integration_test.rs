@@ -15,6 +15,33 @@
 #![warn(unsafe_code)]
 
 use getoptsargs::testutils::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+/// Creates a directory for a test called `name` with a symbolic link to the `multicall` example
+/// named after `applet`, and returns the directory.
+#[cfg(unix)]
+fn create_applet_link(name: &str, applet: &str) -> TestDir {
+    let dir = TestDir::new(name);
+    std::os::unix::fs::symlink(bin_path("examples/multicall"), dir.path().join(applet)).unwrap();
+    dir
+}
+
+/// Creates a directory for a test called `name` with a fake external subcommand for the
+/// `subcommands` example that prints its arguments and exits with code 7.
+#[cfg(unix)]
+fn create_external_subcommand(name: &str) -> TestDir {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TestDir::new(name);
+    let path = dir.path().join("subcommands-hello");
+    fs::write(&path, "#! /bin/sh\necho \"Hello: $*\"\nexit 7\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
 
 #[test]
 fn test_async_no_args_no_output() {
@@ -167,38 +194,264 @@ File name: f3
 }
 
 #[test]
-fn test_everything_help() {
+fn test_everything_choice() {
     check(
         bin_path("examples/everything"),
-        &["--help"],
+        &["--color=never", "a", "b", "c"],
         0,
+        Behavior::Inline("Color: never\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["--color", "sometimes", "a", "b", "c"],
+        2,
+        Behavior::Null,
         Behavior::Inline(
-            "Usage: everything [options] first second third_has_a_very_long_name [name1 .. nameN]
+            "Usage error: Invalid value `sometimes` for option --color: must be one of: auto, \
+always, never
+Type `everything --help` or `man 8 the-everything` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
 
-Options:
-    -h, --help          show command-line usage information and exit
-        --version       show version information and exit
-    -p, --print-args    print free arguments
+#[test]
+fn test_everything_exclusive() {
+    check(
+        bin_path("examples/everything"),
+        &["--yaml", "a", "b", "c"],
+        0,
+        Behavior::Inline("Format: YAML\n".to_owned()),
+        Behavior::Null,
+    );
 
-Arguments:
-    first               this is the first required argument and contains a
-                        very long description
-    second              short description
-    third_has_a_very_long_name
-                        and a short description
-    [name1 .. nameN]    file names
+    check(
+        bin_path("examples/everything"),
+        &["--yaml", "a", "--json", "b", "c"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Options --json and --yaml are mutually exclusive
+Type `everything --help` or `man 8 the-everything` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_everything_requires_and_conflicts() {
+    check(
+        bin_path("examples/everything"),
+        &["-o", "out", "a", "b", "c", "d"],
+        0,
+        Behavior::Inline("Output: out\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["-p", "-o", "out", "a", "b", "c"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Option --output conflicts with option --print-args; Option --output \
+requires exactly 1 `name` argument
+Type `everything --help` or `man 8 the-everything` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_everything_bool() {
+    check(
+        bin_path("examples/everything"),
+        &["--headers", "--no-headers", "a", "b", "c"],
+        0,
+        Behavior::Inline("Headers: disabled\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["--no-headers", "a", "b", "c", "--headers"],
+        0,
+        Behavior::Null,
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_everything_map() {
+    check(
+        bin_path("examples/everything"),
+        &["-Dz=1", "a", "--define", "m=2=3", "b", "c"],
+        0,
+        Behavior::Inline("Variable: m = 2=3\nVariable: z = 1\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["-Da=1", "-Da=2", "a", "b", "c"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Duplicate key `a` for option --define
+Type `everything --help` or `man 8 the-everything` for more information
+"
+            .to_owned(),
+        ),
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["-Da", "a", "b", "c"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Invalid value `a` for option --define: must be of the form KEY=VALUE
+Type `everything --help` or `man 8 the-everything` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_everything_list() {
+    check(
+        bin_path("examples/everything"),
+        &["--features=x,y", "a", "b", "c", "--features", "z"],
+        0,
+        Behavior::Inline("Features: x y z\n".to_owned()),
+        Behavior::Null,
+    );
+}
 
-This is an extra help message.
+#[test]
+fn test_everything_units() {
+    check(
+        bin_path("examples/everything"),
+        &["--timeout=1m1.5s", "a", "b", "c"],
+        0,
+        Behavior::Inline("Timeout: 61500ms; buffer size: 65536 bytes\n".to_owned()),
+        Behavior::Null,
+    );
 
-Report bugs to: https://example.com/everything/issues/
-Everything home page: https://everything.example.com/
+    check(
+        bin_path("examples/everything"),
+        &["--buffer-size", "1.5k", "a", "b", "c"],
+        0,
+        Behavior::Inline("Timeout: 150000ms; buffer size: 1500 bytes\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/everything"),
+        &["--timeout", "5 minutes", "a", "b", "c"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Invalid value `5 minutes` for option --timeout: unknown unit ` minutes`
+Type `everything --help` or `man 8 the-everything` for more information
 "
             .to_owned(),
         ),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_everything_non_utf8_args() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let output = process::Command::new(bin_path("examples/everything"))
+        .args([OsStr::new("a"), OsStr::from_bytes(b"b\xff"), OsStr::new("c")])
+        .output()
+        .unwrap();
+    assert_eq!("", String::from_utf8(output.stdout).unwrap());
+    assert_eq!(
+        "Usage error: Invalid UTF-8 in argument `b\u{FFFD}`
+Type `everything --help` or `man 8 the-everything` for more information
+",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn test_everything_private_use_args() {
+    check(
+        bin_path("examples/everything"),
+        &["-p", "a", "b", "c\u{10FFFF}"],
+        42,
+        Behavior::Inline("First arg: a\nSecond arg: b\nThird arg: c\u{10FFFF}\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_everything_help() {
+    check(
+        bin_path("examples/everything"),
+        &["--help"],
+        0,
+        Behavior::File(PathBuf::from("tests/golden/everything-help.txt")),
         Behavior::Null,
     );
 }
 
+#[test]
+fn test_everything_response_files() {
+    let dir = TestDir::new("response-files");
+    let path = dir.path().join("args");
+    fs::write(&path, "# Arguments for everything\n-p abc 'de fg'\n").unwrap();
+
+    let mut child = process::Command::new(bin_path("examples/everything"))
+        .args([&format!("@{}", path.display()), "@-", "f2"])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"h \"f1\"\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(42), output.status.code());
+    assert_eq!(
+        "First arg: abc\nSecond arg: de fg\nThird arg: h\nFile name: f1\nFile name: f2\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn test_everything_response_files_missing() {
+    let dir = TestDir::new("response-files-missing");
+    let path = dir.path().join("missing");
+
+    let prefix = format!("Usage error: Cannot read response file {}: ", path.display());
+    check(
+        bin_path("examples/everything"),
+        &[&format!("@{}", path.display())],
+        2,
+        Behavior::Null,
+        Behavior::Lines(vec![
+            Matcher::Predicate(Box::new(move |line| line.starts_with(&prefix))),
+            Matcher::Exact(
+                "Type `everything --help` or `man 8 the-everything` for more information"
+                    .to_owned(),
+            ),
+        ]),
+    );
+}
+
 #[test]
 fn test_imperative_no_args_no_output() {
     check(bin_path("examples/imperative"), &["a"], 0, Behavior::Null, Behavior::Null);
@@ -220,6 +473,24 @@ Free argument: de fg
     );
 }
 
+#[cfg(unix)]
+#[test]
+fn test_imperative_non_utf8_args() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let output = process::Command::new(bin_path("examples/imperative"))
+        .args([OsStr::new("--print-args"), OsStr::from_bytes(b"a\xffb"), OsStr::new("c")])
+        .output()
+        .unwrap();
+    assert_eq!(
+        "Free argument (not UTF-8): \"a\\xFFb\"\nFree argument: c\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+    assert_eq!(Some(0), output.status.code());
+}
+
 #[test]
 fn test_imperative_help() {
     check(
@@ -243,3 +514,495 @@ Arguments:
         Behavior::Null,
     );
 }
+
+#[cfg(unix)]
+#[test]
+fn test_multicall_by_program_name() {
+    let dir = create_applet_link("multicall-by-program-name", "upper");
+    check(
+        dir.path().join("upper"),
+        &["Hello", "World"],
+        0,
+        Behavior::Inline("HELLO WORLD\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_multicall_by_program_name_usage_error() {
+    let dir = create_applet_link("multicall-by-program-name-usage-error", "lower");
+    check(
+        dir.path().join("lower"),
+        &["--foo"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Unrecognized option: 'foo'
+Type `lower --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_multicall_by_first_argument() {
+    check(
+        bin_path("examples/multicall"),
+        &["lower", "Hello", "World"],
+        0,
+        Behavior::Inline("hello world\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_multicall_unknown_applet() {
+    check(
+        bin_path("examples/multicall"),
+        &["foo"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Unknown command `foo`
+Type `multicall --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_no_command() {
+    check(
+        bin_path("examples/subcommands"),
+        &[],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: No command specified
+Type `subcommands --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_unknown_command() {
+    check(
+        bin_path("examples/subcommands"),
+        &["foo"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Unknown command `foo`
+Type `subcommands --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_dispatch() {
+    check(
+        bin_path("examples/subcommands"),
+        &["add", "-f", "abc"],
+        0,
+        Behavior::Inline("Replacing abc\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/subcommands"),
+        &["remove", "abc", "def"],
+        0,
+        Behavior::Inline("Removing abc\nRemoving def\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_positional_after_trailing() {
+    check(
+        bin_path("examples/subcommands"),
+        &["copy", "a", "b", "c"],
+        0,
+        Behavior::Inline("Copying a into c\nCopying b into c\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/subcommands"),
+        &["copy", "a"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Trailing argument `source` requires at least 1 value
+Type `subcommands copy --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_usage_error() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remove"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Trailing argument `name` requires at least 1 value
+Type `subcommands remove --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_help() {
+    check(
+        bin_path("examples/subcommands"),
+        &["--help"],
+        0,
+        Behavior::Inline(
+            "Usage: subcommands [options] command [args]
+
+Options:
+    -h, --help          show command-line usage information and exit
+        --version       show version information and exit
+    -v, --verbose       increase verbosity; can be given multiple times [env:
+                        SUBCOMMANDS_VERBOSE]
+    -q, --quiet         decrease verbosity; can be given multiple times
+        --config FILE   read configuration from FILE
+
+Commands:
+    add                 adds a new entry
+    remove              removes one or more entries
+    copy                copies one or more entries
+    remote              manages remotes
+
+"
+            .to_owned(),
+        ),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_subcommand_help() {
+    check(
+        bin_path("examples/subcommands"),
+        &["add", "--help"],
+        0,
+        Behavior::Inline(
+            "Usage: subcommands add [options] name
+
+Options:
+    -h, --help          show command-line usage information and exit
+    -f, --force         replace the entry if it already exists
+
+Global options:
+        --version       show version information and exit
+    -v, --verbose       increase verbosity; can be given multiple times [env:
+                        SUBCOMMANDS_VERBOSE]
+    -q, --quiet         decrease verbosity; can be given multiple times
+        --config FILE   read configuration from FILE
+
+Arguments:
+    name                name of the entry to add
+
+"
+            .to_owned(),
+        ),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_nested_dispatch() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remote"],
+        0,
+        Behavior::Inline("No remotes configured\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/subcommands"),
+        &["remote", "add", "origin", "https://example.com/"],
+        0,
+        Behavior::Inline("Adding remote origin at https://example.com/ tracking main\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_nested_global_options() {
+    check(
+        bin_path("examples/subcommands"),
+        &["-v", "remote", "--verbose", "add", "-v", "origin", "https://example.com/"],
+        0,
+        Behavior::Inline(
+            "Adding remote origin at https://example.com/ tracking main
+Verbosity level: 3
+"
+            .to_owned(),
+        ),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_verbosity_flags() {
+    check(
+        bin_path("examples/subcommands"),
+        &["-vvv", "add", "-q", "foo"],
+        0,
+        Behavior::Inline("Adding foo\nVerbosity level: 2\n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("examples/subcommands"),
+        &["-v", "add", "-q", "foo"],
+        0,
+        Behavior::Inline("Adding foo\n".to_owned()),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_nested_usage_error() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remote", "add", "origin"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Required argument `url` not provided
+Type `subcommands remote add --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_nested_optional_argument() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remote", "add", "origin", "https://example.com/", "stable"],
+        0,
+        Behavior::Inline(
+            "Adding remote origin at https://example.com/ tracking stable\n".to_owned(),
+        ),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_nested_validator() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remote", "add", "origin", "example.com"],
+        2,
+        Behavior::Null,
+        Behavior::Inline(
+            "Usage error: Invalid value `example.com` for argument `url`: must be a URL
+Type `subcommands remote add --help` for more information
+"
+            .to_owned(),
+        ),
+    );
+}
+
+#[test]
+fn test_subcommands_nested_help() {
+    check(
+        bin_path("examples/subcommands"),
+        &["remote", "add", "--help"],
+        0,
+        Behavior::Inline(
+            "Usage: subcommands remote add [options] name url [branch]
+
+Options:
+    -h, --help          show command-line usage information and exit
+
+Global options:
+        --version       show version information and exit
+    -v, --verbose       increase verbosity; can be given multiple times [env:
+                        SUBCOMMANDS_VERBOSE]
+    -q, --quiet         decrease verbosity; can be given multiple times
+        --config FILE   read configuration from FILE
+
+Arguments:
+    name                name of the remote
+    url                 location of the remote
+    [branch]            branch to track (default: main)
+
+"
+            .to_owned(),
+        ),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_subcommands_env_fallback() {
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["add", "foo"])
+        .env("SUBCOMMANDS_VERBOSE", "yes")
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Adding foo\nVerbosity level: 1\n", String::from_utf8(output.stdout).unwrap());
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn test_subcommands_env_fallback_command_line_wins() {
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["-vv", "add", "foo"])
+        .env("SUBCOMMANDS_VERBOSE", "invalid")
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Adding foo\nVerbosity level: 2\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_subcommands_env_fallback_invalid() {
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["add", "foo"])
+        .env("SUBCOMMANDS_VERBOSE", "invalid")
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("", String::from_utf8(output.stdout).unwrap());
+    assert_eq!(
+        "Usage error: Invalid boolean value `invalid` in environment variable SUBCOMMANDS_VERBOSE \
+for option --verbose\nType `subcommands add --help` for more information\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[test]
+fn test_subcommands_config_files() {
+    let dir = TestDir::new("config-files");
+    fs::create_dir_all(dir.path().join("subcommands")).unwrap();
+    fs::write(dir.path().join("subcommands/config"), "verbose = yes\n[add]\nforce = no\n").unwrap();
+    fs::write(dir.path().join("explicit"), "[add]\nforce = yes\n").unwrap();
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["add", "foo"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Adding foo\nVerbosity level: 1\n", String::from_utf8(output.stdout).unwrap());
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["--config", dir.path().join("explicit").to_str().unwrap(), "add", "foo"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Replacing foo\nVerbosity level: 1\n", String::from_utf8(output.stdout).unwrap());
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["add", "foo"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("SUBCOMMANDS_VERBOSE", "no")
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Adding foo\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_subcommands_config_files_other_command() {
+    let dir = TestDir::new("config-files-other-command");
+    fs::create_dir_all(dir.path().join("subcommands")).unwrap();
+    fs::write(dir.path().join("subcommands/config"), "force = yes\n").unwrap();
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["add", "foo"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Replacing foo\n", String::from_utf8(output.stdout).unwrap());
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["remove", "foo"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Removing foo\n", String::from_utf8(output.stdout).unwrap());
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn test_subcommands_config_files_error() {
+    let dir = TestDir::new("config-files-error");
+    let path = dir.path().join("explicit");
+    fs::write(&path, "# Comment\n[remote]\nforce = yes\n").unwrap();
+
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["--config", path.to_str().unwrap(), "remote"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("", String::from_utf8(output.stdout).unwrap());
+    assert_eq!(
+        format!(
+            "Usage error: Unknown option `force` in {}:3\n\
+Type `subcommands remote --help` for more information\n",
+            path.display()
+        ),
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_subcommands_external_dispatch() {
+    let dir = create_external_subcommand("external-dispatch");
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .args(["-v", "hello", "a", "--verbose", "b"])
+        .env("PATH", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(7), output.status.code());
+    assert_eq!("Hello: a --verbose b\n", String::from_utf8(output.stdout).unwrap());
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_subcommands_external_help() {
+    let dir = create_external_subcommand("external-help");
+    let output = process::Command::new(bin_path("examples/subcommands"))
+        .arg("--help")
+        .env("PATH", dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("\nExternal commands:\n    hello\n\n"), "Got: {}", stdout);
+}